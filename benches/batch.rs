@@ -0,0 +1,71 @@
+//! Compares evaluating every creature's brain on its own with [`BrainBatch`] at several
+//! population sizes. Every brain has its own weights, so both read the same weights and compute
+//! the same activations, and they come out about even.
+//!
+//! Run with `cargo bench --bench batch`.
+
+extern crate blas_src;
+
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use rust_creatures::model::{ActionSelection, BrainBatch, Creature, Observation, WorldConfig};
+use std::time::{Duration, Instant};
+
+const TICKS: u32 = 200;
+
+fn population(n: usize, config: &WorldConfig) -> Vec<Creature> {
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    (0..n)
+        .map(|id| Creature::new(id, id, (0., 0., 0.), 0.5, 0, config, &mut rng))
+        .collect()
+}
+
+fn observations(n: usize) -> Vec<Observation> {
+    (0..n)
+        .map(|i| {
+            let mut o = Observation::new_empty();
+            o.colors[i % Observation::MAX_SITES] = 1.;
+            o.dists[i % Observation::MAX_SITES] = 5.;
+            o.energy = 1.;
+            o
+        })
+        .collect()
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    (0..TICKS).for_each(|_| f());
+    start.elapsed() / TICKS
+}
+
+fn main() {
+    let config = WorldConfig::default();
+    println!("brains per tick: per creature / batched");
+    [100, 500, 2000, 5000].iter().for_each(|n| {
+        let obs = observations(*n);
+
+        let mut cs = population(*n, &config);
+        let single = time(|| {
+            let _: Vec<_> = cs
+                .par_iter_mut()
+                .zip(obs.clone().into_par_iter())
+                .map(|(c, o)| c.get_preferred_action(o, ActionSelection::Argmax))
+                .collect();
+        });
+
+        let mut cs = population(*n, &config);
+        let mut batch = BrainBatch::default();
+        let batched = time(|| {
+            batch.get_preferred_actions(&mut cs, obs.clone(), ActionSelection::Argmax);
+        });
+
+        println!(
+            "{:>5} creatures: {:>9.1?} / {:>9.1?} ({:.2}x)",
+            n,
+            single,
+            batched,
+            single.as_secs_f64() / batched.as_secs_f64()
+        );
+    });
+}
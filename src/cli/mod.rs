@@ -0,0 +1,179 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rust_creatures::model::EventFormat;
+use std::path::PathBuf;
+
+pub const DEFAULT_HEADLESS_TICKS: u64 = 1_000_000;
+
+#[derive(Parser)]
+#[command(
+    name = "rust_creatures",
+    version,
+    about = "Simulate a little evolution at home."
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Simulate a new world in a window.
+    #[cfg(feature = "gui")]
+    Run {
+        #[command(flatten)]
+        world: WorldArgs,
+        /// Close the window after this many ticks.
+        #[arg(long)]
+        ticks: Option<u64>,
+        #[command(flatten)]
+        gui: GuiArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Simulate a new world as fast as possible without a window.
+    Headless {
+        #[command(flatten)]
+        world: WorldArgs,
+        /// Number of ticks to simulate.
+        #[arg(long, default_value_t = DEFAULT_HEADLESS_TICKS)]
+        ticks: u64,
+        #[command(flatten)]
+        headless: HeadlessArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    /// Continue a world from a snapshot, adding to the stats and events in the output directory.
+    Resume {
+        /// Snapshot written by a previous run.
+        snapshot: PathBuf,
+        /// Continue without a window.
+        #[arg(long)]
+        headless: bool,
+        /// Stop after this many more ticks, headless runs default to 1000000.
+        #[arg(long)]
+        ticks: Option<u64>,
+        /// Override the minimum population stored in the snapshot.
+        #[arg(long)]
+        min_pop: Option<usize>,
+        /// Override the minimum number of families stored in the snapshot.
+        #[arg(long)]
+        min_fams: Option<usize>,
+        #[cfg(feature = "gui")]
+        #[command(flatten)]
+        gui: GuiArgs,
+        #[command(flatten)]
+        headless_args: HeadlessArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+#[derive(Args)]
+pub struct WorldArgs {
+    /// World width in grass tiles.
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub width: u64,
+    /// World height in grass tiles.
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub height: u64,
+    /// Spawn creatures whenever the population drops below this.
+    #[arg(long, default_value_t = 20)]
+    pub min_pop: usize,
+    /// Spawn founders whenever fewer families than this are alive.
+    #[arg(long, default_value_t = 5)]
+    pub min_fams: usize,
+    /// Seed for the world's random stream, random if not given.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// TOML (or .json) file of simulation constants, see config.example.toml.
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Args)]
+pub struct GuiArgs {
+    /// Frames drawn per second.
+    #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u32).range(1..))]
+    pub draw_hz: u32,
+    /// Simulation ticks per second.
+    #[arg(long, default_value_t = 6000, value_parser = clap::value_parser!(u32).range(1..))]
+    pub hz: u32,
+}
+
+#[derive(Args)]
+pub struct HeadlessArgs {
+    /// Print progress every this many ticks.
+    #[arg(long, default_value_t = 1000)]
+    pub report_every: u64,
+    /// Also save a snapshot to the output directory every this many ticks.
+    #[arg(long, requires = "output_dir")]
+    pub snapshot_every: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct OutputArgs {
+    /// Directory to write the final snapshot (snapshot.rcw), family tree (lineage.nwk),
+    /// statistics (stats.csv) and event log into.
+    #[arg(long)]
+    pub output_dir: Option<PathBuf>,
+    /// Write a row of statistics every this many ticks.
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub stats_every: u64,
+    /// Log every birth, death, meal and bite to events.jsonl or events.bin.
+    #[arg(long, value_enum, requires = "output_dir")]
+    pub events: Option<EventsFormat>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum EventsFormat {
+    /// One JSON object per line.
+    Json,
+    /// Compact bincode.
+    Binary,
+}
+
+impl OutputArgs {
+    pub const SNAPSHOT_FILE: &'static str = "snapshot.rcw";
+    pub const STATS_FILE: &'static str = "stats.csv";
+    pub const LINEAGE_FILE: &'static str = "lineage.nwk";
+    pub const EVENTS_JSON_FILE: &'static str = "events.jsonl";
+    pub const EVENTS_BINARY_FILE: &'static str = "events.bin";
+
+    pub fn lineage_path(&self) -> Option<PathBuf> {
+        self.output_dir
+            .as_ref()
+            .map(|dir| dir.join(Self::LINEAGE_FILE))
+    }
+
+    pub fn stats_path(&self) -> Option<PathBuf> {
+        self.output_dir
+            .as_ref()
+            .map(|dir| dir.join(Self::STATS_FILE))
+    }
+
+    pub fn events_log(&self) -> Option<(PathBuf, EventFormat)> {
+        let (file, format) = match self.events? {
+            EventsFormat::Json => (Self::EVENTS_JSON_FILE, EventFormat::JsonLines),
+            EventsFormat::Binary => (Self::EVENTS_BINARY_FILE, EventFormat::Binary),
+        };
+        self.output_dir.as_ref().map(|dir| (dir.join(file), format))
+    }
+
+    pub fn snapshot_path(&self) -> Option<PathBuf> {
+        self.output_dir
+            .as_ref()
+            .map(|dir| dir.join(Self::SNAPSHOT_FILE))
+    }
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn verify_cli() {
+        Cli::command().debug_assert();
+    }
+}
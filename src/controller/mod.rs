@@ -1,8 +1,9 @@
-use crate::model::World;
 use crate::view::View;
-use ndarray_rand::rand::rngs::ThreadRng;
+use rust_creatures::model::{StatsWriter, World};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
+use std::fs::File;
+use std::io::BufWriter;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub struct Controller {
@@ -10,6 +11,8 @@ pub struct Controller {
     view: View,
     draw_hertz: u32,
     hertz: u32,
+    max_ticks: Option<u64>,
+    stats: Option<(StatsWriter<BufWriter<File>>, u64)>,
 }
 
 impl Controller {
@@ -19,9 +22,30 @@ impl Controller {
             view: v,
             draw_hertz: 60,
             hertz: 6000,
+            max_ticks: None,
+            stats: None,
         }
     }
 
+    /// Write a row of statistics every `every` ticks.
+    pub fn set_stats(&mut self, writer: StatsWriter<BufWriter<File>>, every: u64) {
+        self.stats = Some((writer, every.max(1)));
+    }
+
+    pub fn set_hertz(&mut self, draw_hertz: u32, hertz: u32) {
+        self.draw_hertz = draw_hertz.max(1);
+        self.hertz = hertz.max(1);
+    }
+
+    /// Stop the loop after this many updates.
+    pub fn set_max_ticks(&mut self, max_ticks: Option<u64>) {
+        self.max_ticks = max_ticks;
+    }
+
+    pub fn get_world(&self) -> &World {
+        &self.world
+    }
+
     pub fn run_loop(&mut self) -> bool {
         self.view.present(&self.world).unwrap();
 
@@ -30,10 +54,10 @@ impl Controller {
         let mut move_left = false;
         let mut move_right = false;
 
-        let mut rng: ThreadRng = ThreadRng::default();
         let mut last_draw = UNIX_EPOCH;
         let mut last_update = UNIX_EPOCH;
-        'running: loop {
+        let mut ticks = 0;
+        let quit = 'running: loop {
             let now_time = SystemTime::now();
             let since_last_draw = now_time
                 .duration_since(last_draw)
@@ -48,10 +72,23 @@ impl Controller {
             }
             if since_last_update > Duration::new(0, 1_000_000_000u32 / self.hertz) {
                 last_update = now_time;
-                self.world.update(&mut rng);
+                self.world.update();
+                if let Some((writer, every)) = &mut self.stats {
+                    if self.world.get_tick().is_multiple_of(*every) {
+                        if let Err(e) = writer.write(&self.world.collect_stats()) {
+                            eprintln!("Could not write stats: {}", e);
+                        }
+                    }
+                }
+                ticks += 1;
+                if self.max_ticks.map(|m| ticks >= m).unwrap_or(false) {
+                    break 'running false;
+                }
             }
 
             let mut toggle_v = false;
+            let mut toggle_c = false;
+            let mut toggle_s = false;
 
             let mut diff_scale = 1.0;
             for event in self.view.get_events() {
@@ -129,6 +166,16 @@ impl Controller {
                         repeat: false,
                         ..
                     } => toggle_v = true,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::C),
+                        repeat: false,
+                        ..
+                    } => toggle_c = true,
+                    Event::KeyDown {
+                        keycode: Some(Keycode::S),
+                        repeat: false,
+                        ..
+                    } => toggle_s = true,
                     _ => {}
                 }
             }
@@ -154,7 +201,23 @@ impl Controller {
             if toggle_v {
                 self.view.toggle_vision();
             }
+            if toggle_c {
+                self.view.toggle_body_color();
+            }
+            if toggle_s {
+                self.view.toggle_scent();
+            }
             ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / self.hertz));
+        };
+
+        if let Some((writer, _)) = &mut self.stats {
+            if let Err(e) = writer.flush() {
+                eprintln!("Could not write stats: {}", e);
+            }
+        }
+        if let Err(e) = self.world.flush_events() {
+            eprintln!("Could not write events: {}", e);
         }
+        quit
     }
 }
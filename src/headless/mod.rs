@@ -0,0 +1,129 @@
+use rust_creatures::model::{StatsWriter, World};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::time::Instant;
+
+pub struct Headless {
+    world: World,
+    ticks: u64,
+    report_every: u64,
+    checkpoint: Option<(PathBuf, u64)>,
+    stats: Option<(StatsWriter<BufWriter<File>>, u64)>,
+}
+
+impl Headless {
+    pub fn new(w: World, ticks: u64) -> Self {
+        Self {
+            world: w,
+            ticks,
+            report_every: 1000,
+            checkpoint: None,
+            stats: None,
+        }
+    }
+
+    /// Write a row of statistics every `every` ticks.
+    pub fn set_stats(&mut self, writer: StatsWriter<BufWriter<File>>, every: u64) {
+        self.stats = Some((writer, every.max(1)));
+    }
+
+    /// Save a snapshot to `path` every `every` ticks, so long runs survive being killed.
+    pub fn set_checkpoint(&mut self, path: PathBuf, every: u64) {
+        self.checkpoint = Some((path, every.max(1)));
+    }
+
+    pub fn set_report_every(&mut self, report_every: u64) {
+        self.report_every = report_every.max(1);
+    }
+
+    pub fn get_world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn run_loop(&mut self) {
+        let start = Instant::now();
+        let mut last_report = start;
+        let mut last_report_tick = 0;
+        for tick in 1..=self.ticks {
+            self.world.update();
+
+            if let Some((writer, every)) = &mut self.stats {
+                if self.world.get_tick().is_multiple_of(*every) {
+                    if let Err(e) = writer.write(&self.world.collect_stats()) {
+                        eprintln!("Could not write stats: {}", e);
+                    }
+                }
+            }
+
+            if tick.is_multiple_of(self.report_every) || tick == self.ticks {
+                let now = Instant::now();
+                let elapsed = now.duration_since(last_report).as_secs_f64();
+                let rate = (tick - last_report_tick) as f64 / elapsed.max(f64::EPSILON);
+                println!(
+                    "tick {}/{}: {} creatures, {} families, {:.0} ticks/s",
+                    tick,
+                    self.ticks,
+                    self.world.num_creatures(),
+                    self.world.num_families(),
+                    rate
+                );
+                last_report = now;
+                last_report_tick = tick;
+            }
+
+            if let Some((path, every)) = &self.checkpoint {
+                if self.world.get_tick().is_multiple_of(*every) {
+                    if let Err(e) = self.world.save_snapshot_file(path) {
+                        eprintln!("Could not save checkpoint {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+
+        if let Some((writer, _)) = &mut self.stats {
+            if let Err(e) = writer.flush() {
+                eprintln!("Could not write stats: {}", e);
+            }
+        }
+        if let Err(e) = self.world.flush_events() {
+            eprintln!("Could not write events: {}", e);
+        }
+
+        let total = start.elapsed().as_secs_f64();
+        println!(
+            "Finished {} ticks in {:.1}s ({:.0} ticks/s)",
+            self.ticks,
+            total,
+            self.ticks as f64 / total.max(f64::EPSILON)
+        );
+    }
+}
+
+#[cfg(test)]
+mod headless_tests {
+    use super::*;
+
+    #[test]
+    fn runs_requested_ticks() {
+        let mut h = Headless::new(World::new_seeded(20, 20, 10, 0), 25);
+        h.set_report_every(10);
+        h.run_loop();
+        assert_eq!(h.get_world().get_tick(), 25);
+        assert!(h.get_world().num_creatures() >= 10);
+    }
+
+    #[test]
+    fn checkpoints_on_world_ticks() {
+        // A resumed world starts past tick 0, checkpoints still land on multiples of the cadence.
+        let mut w = World::new_seeded(20, 20, 10, 0);
+        (0..3).for_each(|_| w.update());
+        let path = std::env::temp_dir().join(format!("rcw_checkpoint_{}.rcw", std::process::id()));
+        let mut h = Headless::new(w, 12);
+        h.set_checkpoint(path.clone(), 10);
+        h.run_loop();
+        assert_eq!(h.get_world().get_tick(), 15);
+        assert_eq!(World::load_snapshot_file(&path).unwrap().get_tick(), 10);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,24 @@
+//! Evolution simulation of little neural-network driven creatures.
+//!
+//! The simulation lives in [`model`]: a [`World`](model::World) holds a toroidal grass field and
+//! the [`Creature`](model::Creature)s grazing on it, each controlled by a
+//! [`NeuralBrain`](model::NeuralBrain). Rendering and input handling are left to consumers, such
+//! as the SDL front end in the `rust_creatures` binary.
+//!
+//! ```no_run
+//! use rust_creatures::model::World;
+//!
+//! let mut world = World::new_seeded(100, 100, 20, 1234);
+//! for _ in 0..1000 {
+//!     world.update();
+//! }
+//! for c in world.get_creature_slice() {
+//!     let (x, y, _) = c.get_pos();
+//!     println!("{} (family {}) at ({:.1}, {:.1})", c.get_id(), c.get_fam(), x, y);
+//! }
+//! ```
+
+extern crate blas_src;
+
+pub mod model;
+pub mod util;
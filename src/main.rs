@@ -1,13 +1,178 @@
-extern crate blas_src;
-
+mod cli;
+#[cfg(feature = "gui")]
 mod controller;
-mod model;
-mod util;
+mod headless;
+#[cfg(feature = "gui")]
 mod view;
 
+use clap::Parser;
+use cli::{Cli, Command, HeadlessArgs, OutputArgs, WorldArgs};
+use rust_creatures::model::{EventLog, StatsWriter, World, WorldConfig};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
 fn main() {
-    let w = model::World::new(100, 100, 20);
+    let cli = Cli::parse();
+
+    match cli.command {
+        #[cfg(feature = "gui")]
+        Command::Run {
+            world,
+            ticks,
+            gui,
+            output,
+        } => {
+            let w = new_world(&world);
+            prepare_output(&output);
+            run_gui(w, ticks, &gui, &output, false);
+        }
+        Command::Headless {
+            world,
+            ticks,
+            headless,
+            output,
+        } => {
+            let w = new_world(&world);
+            prepare_output(&output);
+            run_headless(w, ticks, &headless, &output, false);
+        }
+        Command::Resume {
+            snapshot,
+            headless,
+            ticks,
+            min_pop,
+            min_fams,
+            #[cfg(feature = "gui")]
+            gui,
+            headless_args,
+            output,
+        } => {
+            let mut w = or_exit(World::load_snapshot_file(&snapshot), &snapshot);
+            println!("Resuming from {}", snapshot.display());
+            if let Some(min_pop) = min_pop {
+                w.set_min_pop(min_pop);
+            }
+            if let Some(min_fams) = min_fams {
+                w.set_min_fams(min_fams);
+            }
+            prepare_output(&output);
+
+            #[cfg(feature = "gui")]
+            if !headless {
+                run_gui(w, ticks, &gui, &output, true);
+                return;
+            }
+            if !cfg!(feature = "gui") && !headless {
+                eprintln!("Built without the gui feature, resuming headless.");
+            }
+            let ticks = ticks.unwrap_or(cli::DEFAULT_HEADLESS_TICKS);
+            run_headless(w, ticks, &headless_args, &output, true);
+        }
+    }
+}
+
+fn new_world(args: &WorldArgs) -> World {
+    let config = match &args.config {
+        Some(path) => or_exit(WorldConfig::from_file(path), path),
+        None => WorldConfig::default(),
+    };
+    let seed = args.seed.unwrap_or_else(ndarray_rand::rand::random);
+    println!("Seed: {}", seed);
+
+    let mut w = World::new_with_config(
+        args.width as usize,
+        args.height as usize,
+        args.min_pop,
+        seed,
+        config,
+    );
+    w.set_min_fams(args.min_fams);
+    w
+}
+
+#[cfg(feature = "gui")]
+fn run_gui(
+    mut w: World,
+    ticks: Option<u64>,
+    gui: &cli::GuiArgs,
+    output: &OutputArgs,
+    resume: bool,
+) {
+    subscribe_events(&mut w, output, resume);
     let v = view::View::new();
     let mut c = controller::Controller::new(w, v);
+    c.set_hertz(gui.draw_hz, gui.hz);
+    c.set_max_ticks(ticks);
+    if let Some(path) = output.stats_path() {
+        c.set_stats(stats_writer(&path, resume), output.stats_every);
+    }
     c.run_loop();
+    save_world(c.get_world(), output);
+}
+
+fn run_headless(mut w: World, ticks: u64, args: &HeadlessArgs, output: &OutputArgs, resume: bool) {
+    subscribe_events(&mut w, output, resume);
+    let mut h = headless::Headless::new(w, ticks);
+    h.set_report_every(args.report_every);
+    if let (Some(every), Some(path)) = (args.snapshot_every, output.snapshot_path()) {
+        h.set_checkpoint(path, every);
+    }
+    if let Some(path) = output.stats_path() {
+        h.set_stats(stats_writer(&path, resume), output.stats_every);
+    }
+    h.run_loop();
+    save_world(h.get_world(), output);
+}
+
+fn prepare_output(output: &OutputArgs) {
+    if let Some(dir) = &output.output_dir {
+        or_exit(std::fs::create_dir_all(dir), dir);
+    }
+}
+
+// Resumed runs add to the stats and events of the run they continue rather than replacing them.
+fn stats_writer(path: &Path, resume: bool) -> StatsWriter<BufWriter<File>> {
+    let writer = if resume {
+        StatsWriter::append(path)
+    } else {
+        StatsWriter::create(path)
+    };
+    or_exit(writer, path)
+}
+
+fn subscribe_events(w: &mut World, output: &OutputArgs, resume: bool) {
+    if let Some((path, format)) = output.events_log() {
+        let log = if resume {
+            EventLog::append(&path, format)
+        } else {
+            EventLog::create(&path, format)
+        };
+        w.subscribe(Box::new(or_exit(log, &path)));
+    }
+}
+
+fn save_world(w: &World, output: &OutputArgs) {
+    if let Some(path) = output.snapshot_path() {
+        match w.save_snapshot_file(&path) {
+            Ok(()) => println!("Saved snapshot to {}", path.display()),
+            Err(e) => eprintln!("Could not save {}: {}", path.display(), e),
+        }
+    }
+    if let Some(path) = output.lineage_path() {
+        match std::fs::write(&path, w.get_lineage().to_newick()) {
+            Ok(()) => println!("Saved lineage to {}", path.display()),
+            Err(e) => eprintln!("Could not save {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn or_exit<T, E: std::fmt::Display>(res: Result<T, E>, path: &Path) -> T {
+    match res {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
 }
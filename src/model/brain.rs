@@ -1,79 +1,1102 @@
-use ndarray::{arr1, Array, Dim};
+use crate::model::config::WorldConfig;
+use crate::model::neat::NeatBrain;
+use crate::model::recurrent::GruBrain;
+use crate::model::scripted::{Script, ScriptedBrain};
+use ndarray::{concatenate, s, Array, Array1, Array2, Axis, Dim, Dimension};
+use ndarray_rand::rand::{Rng, RngCore};
 use ndarray_rand::rand_distr::Normal;
 use ndarray_rand::RandomExt;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
 use std::cmp::max;
 
+/// Maps `INPUT` values to `OUTPUT` values, and can produce mutated copies of itself.
 pub trait Brain<const INPUT: usize, const OUTPUT: usize> {
-    fn clone_mutate(&self, std: f64) -> Self;
-    fn feed(&self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]);
+    /// A copy with parameters perturbed by noise of standard deviation `std` drawn from `rng`, and
+    /// its structure changed with the chances in `structure` if it has one that evolves.
+    fn clone_mutate<R: Rng + ?Sized>(
+        &self,
+        std: f64,
+        structure: &StructureMutation,
+        rng: &mut R,
+    ) -> Self;
+    /// Evaluate on `inputs`, writing to `outputs` and updating any internal state.
+    fn feed(&mut self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]);
+    /// How far apart two brains are genetically, zero for identical brains.
+    fn genetic_distance(&self, other: &Self) -> f64;
+    /// Number of weights, a measure of how big the brain has evolved to be.
+    fn size(&self) -> usize;
+    /// An offspring combining the genes of this brain and `other` as `mix` chooses, `None` if
+    /// the two are too different to be combined.
+    fn crossover<R: Rng + ?Sized>(
+        &self,
+        other: &Self,
+        mix: &mut Mixer,
+        rng: &mut R,
+    ) -> Option<Self>
+    where
+        Self: Sized;
+    /// Read only the inputs at indices `inputs`, in increasing order, and ignore the rest.
+    ///
+    /// Weights from inputs read before are kept, and inputs read for the first time start without
+    /// any, so the brain computes the same function of the inputs it still reads. Brains which
+    /// always read every input need not implement this.
+    fn set_inputs(&mut self, _inputs: &[usize]) {}
 }
 
-pub struct NeuralBrain<const INPUT: usize, const OUTPUT: usize> {
-    max_size: usize,
-    mats: Vec<Array<f64, Dim<[usize; 2]>>>,
+/// Chances per mutation of each change to the structure of a brain, see the fields of the same
+/// name in [`WorldConfig`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StructureMutation {
+    /// Chance of connecting two unconnected neurons of a [`NeatBrain`].
+    pub add_connection_prob: f64,
+    /// Chance of splitting a connection of a [`NeatBrain`] with a new hidden neuron.
+    pub add_node_prob: f64,
+    /// Chance of enabling or disabling a connection of a [`NeatBrain`].
+    pub toggle_connection_prob: f64,
+    /// Chance of adding a neuron to a hidden layer of a [`NeuralBrain`].
+    pub grow_layer_prob: f64,
+    /// Chance of removing a neuron from a hidden layer of a [`NeuralBrain`].
+    pub shrink_layer_prob: f64,
+    /// Chance of inserting a hidden layer into a [`NeuralBrain`].
+    pub insert_layer_prob: f64,
+    /// Chance of removing a hidden layer from a [`NeuralBrain`].
+    pub remove_layer_prob: f64,
+    /// Most hidden layers a [`NeuralBrain`] can evolve.
+    pub max_hidden_layers: usize,
+    /// Chance of changing the activation of a hidden neuron of a [`NeuralBrain`] or
+    /// [`NeatBrain`].
+    pub change_activation_prob: f64,
 }
 
-impl<const INPUT: usize, const OUTPUT: usize> Default for NeuralBrain<INPUT, OUTPUT> {
+impl Default for StructureMutation {
     fn default() -> Self {
-        Self::new_random(&[7])
+        WorldConfig::default().structure_mutation()
     }
 }
 
+/// A fully connected feed-forward network with biases and a choice of activation per hidden
+/// neuron, the output layer is linear.
+///
+/// Besides perturbing weights and biases, mutation can change a neuron's activation, grow or
+/// shrink hidden layers, and insert or remove whole layers, in ways that change what the brain
+/// computes as little as possible.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NeuralBrain<const INPUT: usize, const OUTPUT: usize> {
+    // Widest a hidden layer may grow, the widest hidden or output layer the brain was created
+    // with. The input layer doesn't count, it can be much wider than anything worth evolving.
+    max_size: usize,
+    // Indices of the inputs read, one per column of the first matrix.
+    inputs: Vec<usize>,
+    mats: Vec<Array<f64, Dim<[usize; 2]>>>,
+    // One per matrix.
+    biases: Vec<Array1<f64>>,
+    // One per hidden layer, with an entry per neuron.
+    activations: Vec<Vec<Activation>>,
+}
+
 impl<const INPUT: usize, const OUTPUT: usize> NeuralBrain<INPUT, OUTPUT> {
-    pub fn new_random(shape: &[usize]) -> Self {
-        let mut max_size = INPUT;
+    /// Random normal weights and biases with hidden layers of the given sizes, reading every input.
+    ///
+    /// Hidden layer `i` uses `activations[i]`, or `tanh` if there are fewer activations than
+    /// layers.
+    pub fn new_random<R: Rng + ?Sized>(
+        shape: &[usize],
+        activations: &[Activation],
+        rng: &mut R,
+    ) -> Self {
+        let mut max_size = 0;
         let mut last_size = INPUT;
         let mut mats = vec![];
+        let mut biases = vec![];
         let d = Normal::new(0., 1.).unwrap();
-        shape.iter().cloned().for_each(|next_size| {
-            let mat = Array::random([next_size, last_size], d);
-            mats.push(mat);
+        shape.iter().cloned().chain([OUTPUT]).for_each(|next_size| {
+            mats.push(Array::random_using([next_size, last_size], d, rng));
+            biases.push(Array::random_using(next_size, d, rng));
             last_size = next_size;
             max_size = max(max_size, next_size);
         });
-        let mat = Array::random([OUTPUT, last_size], d);
-        mats.push(mat);
-        max_size = max(max_size, OUTPUT);
+        let activations = shape
+            .iter()
+            .enumerate()
+            .map(|(i, size)| {
+                let activation = activations.get(i).copied().unwrap_or(Activation::Tanh);
+                vec![activation; *size]
+            })
+            .collect();
 
-        Self { max_size, mats }
+        Self {
+            max_size,
+            inputs: (0..INPUT).collect(),
+            mats,
+            biases,
+            activations,
+        }
+    }
+
+    /// Indices of the inputs read, in the order of the columns of the first weight matrix.
+    pub fn get_inputs(&self) -> &[usize] {
+        &self.inputs
+    }
+
+    /// Weight matrices from input to output, each of shape `[out, in]`.
+    pub fn get_layers(&self) -> &[Array<f64, Dim<[usize; 2]>>] {
+        &self.mats
+    }
+
+    /// Bias vectors, one per weight matrix.
+    pub fn get_biases(&self) -> &[Array1<f64>] {
+        &self.biases
+    }
+
+    /// Activation of every neuron of each hidden layer.
+    pub fn get_activations(&self) -> &[Vec<Activation>] {
+        &self.activations
+    }
+
+    /// Sizes of the hidden layers.
+    pub fn get_hidden_sizes(&self) -> Vec<usize> {
+        self.activations.iter().map(|a| a.len()).collect()
+    }
+
+    /// Evaluate weight matrix `l`, with its bias and activations, on `input` writing to `output`.
+    ///
+    /// [`Brain::feed`] runs every layer in turn, this lets callers do so on their own buffers.
+    pub fn feed_layer(&self, l: usize, input: &[f64], output: &mut [f64]) {
+        let weights = self.mats[l].as_standard_layout();
+        let biases = self.biases[l].as_standard_layout();
+        feed_dense(
+            weights.as_slice().unwrap(),
+            biases.as_slice().unwrap(),
+            self.activations.get(l).map(|a| a.as_slice()),
+            input,
+            output,
+        );
+    }
+
+    fn mutate_structure<R: Rng + ?Sized>(
+        &mut self,
+        std: f64,
+        structure: &StructureMutation,
+        rng: &mut R,
+    ) {
+        let hidden = self.mats.len() - 1;
+        if hidden > 0 && rng.gen_bool(structure.change_activation_prob) {
+            let l = rng.gen_range(0..hidden);
+            let neuron = rng.gen_range(0..self.activations[l].len());
+            self.activations[l][neuron] = Activation::ALL[rng.gen_range(0..Activation::ALL.len())];
+        }
+        if hidden > 0 && rng.gen_bool(structure.grow_layer_prob) {
+            let l = rng.gen_range(0..hidden);
+            if self.mats[l].nrows() < self.max_size {
+                self.grow_layer(l, rng);
+            }
+        }
+        if hidden > 0 && rng.gen_bool(structure.shrink_layer_prob) {
+            let l = rng.gen_range(0..hidden);
+            if self.mats[l].nrows() > 1 {
+                let neuron = rng.gen_range(0..self.mats[l].nrows());
+                self.shrink_layer(l, neuron);
+            }
+        }
+        if hidden < structure.max_hidden_layers && rng.gen_bool(structure.insert_layer_prob) {
+            let at = rng.gen_range(0..=hidden);
+            self.insert_layer(at, std, rng);
+        }
+        if hidden > 0 && rng.gen_bool(structure.remove_layer_prob) {
+            let l = rng.gen_range(0..hidden);
+            self.remove_layer(l);
+        }
+    }
+
+    /// Add a neuron to hidden layer `l` with random inputs and no output, so the brain still
+    /// computes the same function. It takes the activation of the layer's first neuron.
+    fn grow_layer<R: Rng + ?Sized>(&mut self, l: usize, rng: &mut R) {
+        let d = Normal::new(0., 1.).unwrap();
+        let inputs = self.mats[l].ncols();
+        let row = Array::random_using([1, inputs], d, rng);
+        self.mats[l] = concatenate![Axis(0), self.mats[l], row];
+        self.biases[l] = concatenate![Axis(0), self.biases[l], Array::random_using(1, d, rng)];
+        let outputs = self.mats[l + 1].nrows();
+        self.mats[l + 1] = concatenate![Axis(1), self.mats[l + 1], Array2::zeros([outputs, 1])];
+        let activation = self.activations[l][0];
+        self.activations[l].push(activation);
+    }
+
+    /// Remove `neuron` from hidden layer `l`, along with its connections.
+    fn shrink_layer(&mut self, l: usize, neuron: usize) {
+        let keep = (0..self.mats[l].nrows())
+            .filter(|i| *i != neuron)
+            .collect::<Vec<_>>();
+        self.mats[l] = self.mats[l].select(Axis(0), &keep);
+        self.biases[l] = self.biases[l].select(Axis(0), &keep);
+        self.mats[l + 1] = self.mats[l + 1].select(Axis(1), &keep);
+        self.activations[l].remove(neuron);
+    }
+
+    /// Insert a linear hidden layer fed by weight matrix `at`, as wide as that matrix's output.
+    /// It feeds a new matrix initialized to the identity plus noise, which takes over the
+    /// activations of `at`, so the brain computes almost the same function.
+    fn insert_layer<R: Rng + ?Sized>(&mut self, at: usize, std: f64, rng: &mut R) {
+        let size = self.mats[at].nrows();
+        let noise = Array::random_using([size, size], Normal::new(0., std).unwrap(), rng);
+        self.mats.insert(at + 1, Array2::eye(size) + noise);
+        self.biases.insert(at + 1, Array1::zeros(size));
+        self.activations
+            .insert(at, vec![Activation::Identity; size]);
+    }
+
+    /// Remove hidden layer `l`, replacing the weights around it by their product, the linear part
+    /// of what the layer computed.
+    fn remove_layer(&mut self, l: usize) {
+        let next = &self.mats[l + 1];
+        let bias = &self.biases[l + 1] + &next.dot(&self.biases[l]);
+        let merged = next.dot(&self.mats[l]);
+        self.mats[l + 1] = merged;
+        self.biases[l + 1] = bias;
+        self.mats.remove(l);
+        self.biases.remove(l);
+        self.activations.remove(l);
     }
 }
 
 impl<const INPUT: usize, const OUTPUT: usize> Brain<INPUT, OUTPUT> for NeuralBrain<INPUT, OUTPUT> {
-    fn clone_mutate(&self, std: f64) -> Self {
+    fn clone_mutate<R: Rng + ?Sized>(
+        &self,
+        std: f64,
+        structure: &StructureMutation,
+        rng: &mut R,
+    ) -> Self {
         let d = Normal::new(0., std).unwrap();
         let mats = self
             .mats
             .iter()
             .map(|m| {
                 let shape = [m.shape()[0], m.shape()[1]];
-                let diff = Array::random(shape, d);
+                let diff = Array::random_using(shape, d, rng);
                 m + &diff
             })
             .collect::<Vec<_>>();
+        let biases = self
+            .biases
+            .iter()
+            .map(|b| b + &Array::random_using(b.len(), d, rng))
+            .collect::<Vec<_>>();
 
-        Self {
+        let mut brain = Self {
             mats,
+            biases,
+            activations: self.activations.clone(),
+            inputs: self.inputs.clone(),
             max_size: self.max_size,
+        };
+        brain.mutate_structure(std, structure, rng);
+        brain
+    }
+
+    fn feed(&mut self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]) {
+        let read = self.inputs.iter().map(|i| inputs[*i]).collect::<Vec<_>>();
+        let buff = (0..self.mats.len()).fold(read, |buff, l| {
+            let mut out = vec![0.; self.mats[l].nrows()];
+            self.feed_layer(l, &buff, &mut out);
+            out
+        });
+
+        outputs.copy_from_slice(&buff);
+    }
+
+    fn size(&self) -> usize {
+        self.mats.iter().map(|m| m.len()).sum::<usize>()
+            + self.biases.iter().map(|b| b.len()).sum::<usize>()
+    }
+
+    /// Root mean square difference of the weights and biases.
+    ///
+    /// Weights present in only one of the brains, where layer shapes or the inputs read differ,
+    /// are compared to zero.
+    fn genetic_distance(&self, other: &Self) -> f64 {
+        let params = |b: &Self| {
+            let first = spread_inputs(&b.mats[0], &b.inputs, INPUT);
+            std::iter::once(first)
+                .chain(b.mats[1..].iter().cloned())
+                .chain(b.biases.iter().map(|b| b.clone().insert_axis(Axis(1))))
+                .collect::<Vec<_>>()
+        };
+        let (a, b) = (params(self), params(other));
+        rms_distance(&a.iter().collect::<Vec<_>>(), &b.iter().collect::<Vec<_>>())
+    }
+
+    /// Layer by layer, between brains with the same hidden layer sizes only. The offspring reads
+    /// the inputs this brain reads.
+    fn crossover<R: Rng + ?Sized>(
+        &self,
+        other: &Self,
+        mix: &mut Mixer,
+        rng: &mut R,
+    ) -> Option<Self> {
+        if self.get_hidden_sizes() != other.get_hidden_sizes() {
+            return None;
         }
+        let mut brain = self.clone();
+        brain.max_size = max(self.max_size, other.max_size);
+        let first = align_inputs(&self.mats[0], &self.inputs, &other.mats[0], &other.inputs);
+        (0..self.mats.len()).for_each(|l| {
+            mix.next_layer(rng);
+            let theirs = if l == 0 { &first } else { &other.mats[l] };
+            brain.mats[l] = mix.genes(&self.mats[l], theirs, rng);
+            brain.biases[l] = mix.genes(&self.biases[l], &other.biases[l], rng);
+            if let Some(activations) = brain.activations.get_mut(l) {
+                activations
+                    .iter_mut()
+                    .zip(other.activations[l].iter())
+                    .for_each(|(a, b)| *a = mix.pick(*a, *b, rng));
+            }
+        });
+        Some(brain)
     }
 
-    fn feed(&self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]) {
-        let buff = arr1(inputs);
-        let buff = self.mats[..self.mats.len() - 1]
-            .iter()
-            .fold(buff, |buff, mat| {
-                let out = mat.dot(&buff);
-                out.mapv(activation)
-            });
-        let buff = self.mats.last().unwrap().dot(&buff);
-
-        outputs.iter_mut().enumerate().for_each(|(i, o)| {
-            *o = buff[i];
+    fn set_inputs(&mut self, inputs: &[usize]) {
+        if self.inputs != inputs {
+            self.mats[0] = select_inputs(&self.mats[0], &self.inputs, inputs);
+            self.inputs = inputs.to_vec();
+        }
+    }
+}
+
+/// Evaluate a layer with row-major `weights`, a row per output, on `input`: each output is its
+/// bias plus the weighted sum of the inputs, passed through its activation if `activations` are
+/// given.
+///
+/// [`NeuralBrain::feed_layer`] and [`BrainBatch`](crate::model::BrainBatch) both evaluate layers
+/// with this, so their results are exactly the same.
+pub(crate) fn feed_dense(
+    weights: &[f64],
+    biases: &[f64],
+    activations: Option<&[Activation]>,
+    input: &[f64],
+    output: &mut [f64],
+) {
+    let n = input.len();
+    output.iter_mut().enumerate().for_each(|(i, o)| {
+        let sum = biases[i] + dot(&weights[i * n..(i + 1) * n], input);
+        *o = activations.map_or(sum, |a| a[i].apply(sum));
+    });
+}
+
+// Four running sums, so the compiler can vectorize.
+fn dot(a: &[f64], b: &[f64]) -> f64 {
+    let (a4, b4) = (a.chunks_exact(4), b.chunks_exact(4));
+    let tail = a4
+        .remainder()
+        .iter()
+        .zip(b4.remainder())
+        .map(|(x, y)| x * y)
+        .sum::<f64>();
+    let mut sums = [0.; 4];
+    a4.zip(b4)
+        .for_each(|(x, y)| (0..4).for_each(|k| sums[k] += x[k] * y[k]));
+    sums.iter().sum::<f64>() + tail
+}
+
+/// Weights `m` whose first columns read the inputs `old`, rearranged to read `new` instead.
+///
+/// Columns of inputs no longer read are dropped, inputs read for the first time get zero columns
+/// and any columns after the inputs are kept as they are.
+pub(crate) fn select_inputs(m: &Array2<f64>, old: &[usize], new: &[usize]) -> Array2<f64> {
+    let rest = m.ncols() - old.len();
+    let mut selected = Array2::zeros([m.nrows(), new.len() + rest]);
+    new.iter().enumerate().for_each(|(j, i)| {
+        if let Ok(k) = old.binary_search(i) {
+            selected.column_mut(j).assign(&m.column(k));
+        }
+    });
+    selected
+        .slice_mut(s![.., new.len()..])
+        .assign(&m.slice(s![.., old.len()..]));
+    selected
+}
+
+/// The weights `b` of a brain reading inputs `b_inputs`, rearranged to line up with the weights
+/// `a` of a brain reading `a_inputs`, so the two can be combined.
+///
+/// Inputs only `a` reads keep `a`'s weights, the columns after the inputs must match in number.
+pub(crate) fn align_inputs(
+    a: &Array2<f64>,
+    a_inputs: &[usize],
+    b: &Array2<f64>,
+    b_inputs: &[usize],
+) -> Array2<f64> {
+    let mut aligned = a.clone();
+    a_inputs.iter().enumerate().for_each(|(j, i)| {
+        if let Ok(k) = b_inputs.binary_search(i) {
+            aligned.column_mut(j).assign(&b.column(k));
+        }
+    });
+    aligned
+        .slice_mut(s![.., a_inputs.len()..])
+        .assign(&b.slice(s![.., b_inputs.len()..]));
+    aligned
+}
+
+/// Weights `m` whose first columns read the inputs `inputs`, spread out to a column for each of
+/// `width` inputs, zero for inputs not read, followed by the columns after the inputs.
+pub(crate) fn spread_inputs(m: &Array2<f64>, inputs: &[usize], width: usize) -> Array2<f64> {
+    select_inputs(m, inputs, &(0..width).collect::<Vec<_>>())
+}
+
+/// Root mean square difference between two lists of weight matrices.
+///
+/// Weights present in only one of the lists, where shapes differ, are compared to zero.
+pub(crate) fn rms_distance(
+    a: &[&Array<f64, Dim<[usize; 2]>>],
+    b: &[&Array<f64, Dim<[usize; 2]>>],
+) -> f64 {
+    let mut sum = 0.;
+    let mut count = 0;
+    (0..max(a.len(), b.len())).for_each(|l| {
+        let (a, b) = (a.get(l).copied(), b.get(l).copied());
+        let rows = max(a.map_or(0, |m| m.nrows()), b.map_or(0, |m| m.nrows()));
+        let cols = max(a.map_or(0, |m| m.ncols()), b.map_or(0, |m| m.ncols()));
+        let at = |m: Option<&Array<f64, Dim<[usize; 2]>>>, i, j| {
+            m.and_then(|m| m.get([i, j])).copied().unwrap_or(0.)
+        };
+        (0..rows).for_each(|i| {
+            (0..cols).for_each(|j| {
+                let d = at(a, i, j) - at(b, i, j);
+                sum += d * d;
+                count += 1;
+            })
         });
+    });
+    if count == 0 {
+        0.
+    } else {
+        (sum / count as f64).sqrt()
+    }
+}
+
+/// Which [`Brain`] implementation new creatures are born with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrainKind {
+    /// [`NeuralBrain`].
+    #[default]
+    FeedForward,
+    /// [`GruBrain`].
+    Gru,
+    /// [`NeatBrain`].
+    Neat,
+    /// [`ScriptedBrain`] following [`Script::Grazer`].
+    Grazer,
+    /// [`ScriptedBrain`] following [`Script::Predator`].
+    Predator,
+}
+
+/// Object safe counterpart of [`Brain`], so brains of any type can be boxed into an
+/// [`AnyBrain::Custom`].
+///
+/// Implemented for every `Brain` which is `Clone`, `Send`, `Sync` and `'static`.
+pub trait DynBrain<const INPUT: usize, const OUTPUT: usize>: Send + Sync {
+    fn dyn_clone(&self) -> Box<dyn DynBrain<INPUT, OUTPUT>>;
+    fn dyn_clone_mutate(
+        &self,
+        std: f64,
+        structure: &StructureMutation,
+        rng: &mut dyn RngCore,
+    ) -> Box<dyn DynBrain<INPUT, OUTPUT>>;
+    fn dyn_feed(&mut self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]);
+    /// Infinite between brains of different types.
+    fn dyn_genetic_distance(&self, other: &dyn DynBrain<INPUT, OUTPUT>) -> f64;
+    fn dyn_size(&self) -> usize;
+    /// `None` between brains of different types.
+    fn dyn_crossover(
+        &self,
+        other: &dyn DynBrain<INPUT, OUTPUT>,
+        mix: &mut Mixer,
+        rng: &mut dyn RngCore,
+    ) -> Option<Box<dyn DynBrain<INPUT, OUTPUT>>>;
+    fn dyn_set_inputs(&mut self, inputs: &[usize]);
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T, const INPUT: usize, const OUTPUT: usize> DynBrain<INPUT, OUTPUT> for T
+where
+    T: Brain<INPUT, OUTPUT> + Clone + Send + Sync + 'static,
+{
+    fn dyn_clone(&self) -> Box<dyn DynBrain<INPUT, OUTPUT>> {
+        Box::new(self.clone())
+    }
+
+    fn dyn_clone_mutate(
+        &self,
+        std: f64,
+        structure: &StructureMutation,
+        rng: &mut dyn RngCore,
+    ) -> Box<dyn DynBrain<INPUT, OUTPUT>> {
+        Box::new(self.clone_mutate(std, structure, rng))
+    }
+
+    fn dyn_feed(&mut self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]) {
+        self.feed(inputs, outputs)
+    }
+
+    fn dyn_genetic_distance(&self, other: &dyn DynBrain<INPUT, OUTPUT>) -> f64 {
+        match other.as_any().downcast_ref::<T>() {
+            Some(other) => self.genetic_distance(other),
+            None => f64::INFINITY,
+        }
+    }
+
+    fn dyn_size(&self) -> usize {
+        self.size()
+    }
+
+    fn dyn_crossover(
+        &self,
+        other: &dyn DynBrain<INPUT, OUTPUT>,
+        mix: &mut Mixer,
+        rng: &mut dyn RngCore,
+    ) -> Option<Box<dyn DynBrain<INPUT, OUTPUT>>> {
+        let other = other.as_any().downcast_ref::<T>()?;
+        let brain = self.crossover(other, mix, rng)?;
+        Some(Box::new(brain))
+    }
+
+    fn dyn_set_inputs(&mut self, inputs: &[usize]) {
+        self.set_inputs(inputs)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<const INPUT: usize, const OUTPUT: usize> Clone for Box<dyn DynBrain<INPUT, OUTPUT>> {
+    fn clone(&self) -> Self {
+        self.dyn_clone()
+    }
+}
+
+/// Any of the brain implementations, so worlds can choose theirs at runtime and mix them.
+///
+/// Brains of other types, such as hand-written controllers, go in `Custom`. They cannot be
+/// serialized, so worlds holding them cannot be saved.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum AnyBrain<const INPUT: usize, const OUTPUT: usize> {
+    FeedForward(NeuralBrain<INPUT, OUTPUT>),
+    Gru(GruBrain<INPUT, OUTPUT>),
+    Neat(NeatBrain<INPUT, OUTPUT>),
+    Scripted(ScriptedBrain),
+    #[serde(skip)]
+    Custom(Box<dyn DynBrain<INPUT, OUTPUT>>),
+}
+
+impl<const INPUT: usize, const OUTPUT: usize> AnyBrain<INPUT, OUTPUT> {
+    /// A random brain of the given kind, with hidden layers and activations from `config`.
+    ///
+    /// Recurrent brains ignore the activations, their gates are fixed. Scripted brains are not
+    /// random, they only take the constants they need from `config`.
+    pub fn new_random<R: Rng + ?Sized>(kind: BrainKind, config: &WorldConfig, rng: &mut R) -> Self {
+        let shape = &config.brain_hidden_layers;
+        let activations = &config.brain_activations;
+        match kind {
+            BrainKind::FeedForward => {
+                AnyBrain::FeedForward(NeuralBrain::new_random(shape, activations, rng))
+            }
+            BrainKind::Gru => AnyBrain::Gru(GruBrain::new_random(shape, rng)),
+            BrainKind::Neat => AnyBrain::Neat(NeatBrain::new_random(shape, activations, rng)),
+            BrainKind::Grazer => AnyBrain::Scripted(ScriptedBrain::new(Script::Grazer, config)),
+            BrainKind::Predator => AnyBrain::Scripted(ScriptedBrain::new(Script::Predator, config)),
+        }
+    }
+
+    /// Box any other brain type.
+    pub fn custom<B: DynBrain<INPUT, OUTPUT> + 'static>(brain: B) -> Self {
+        AnyBrain::Custom(Box::new(brain))
+    }
+
+    /// Script followed by a scripted brain, `None` for every other brain.
+    pub fn script(&self) -> Option<Script> {
+        match self {
+            AnyBrain::Scripted(b) => Some(b.get_script()),
+            _ => None,
+        }
+    }
+
+    /// Kind of a built in brain, `None` for custom brains.
+    pub fn kind(&self) -> Option<BrainKind> {
+        match self {
+            AnyBrain::FeedForward(_) => Some(BrainKind::FeedForward),
+            AnyBrain::Gru(_) => Some(BrainKind::Gru),
+            AnyBrain::Neat(_) => Some(BrainKind::Neat),
+            AnyBrain::Scripted(b) => match b.get_script() {
+                Script::Grazer => Some(BrainKind::Grazer),
+                Script::Predator => Some(BrainKind::Predator),
+            },
+            AnyBrain::Custom(_) => None,
+        }
     }
 }
 
-fn activation(f: f64) -> f64 {
-    f.tanh()
+impl<const INPUT: usize, const OUTPUT: usize> Brain<INPUT, OUTPUT> for AnyBrain<INPUT, OUTPUT> {
+    fn clone_mutate<R: Rng + ?Sized>(
+        &self,
+        std: f64,
+        structure: &StructureMutation,
+        rng: &mut R,
+    ) -> Self {
+        match self {
+            AnyBrain::FeedForward(b) => AnyBrain::FeedForward(b.clone_mutate(std, structure, rng)),
+            AnyBrain::Gru(b) => AnyBrain::Gru(b.clone_mutate(std, structure, rng)),
+            AnyBrain::Neat(b) => AnyBrain::Neat(b.clone_mutate(std, structure, rng)),
+            AnyBrain::Scripted(b) => {
+                AnyBrain::Scripted(Brain::<INPUT, OUTPUT>::clone_mutate(b, std, structure, rng))
+            }
+            AnyBrain::Custom(b) => {
+                AnyBrain::Custom(b.dyn_clone_mutate(std, structure, &mut { rng }))
+            }
+        }
+    }
+
+    fn feed(&mut self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]) {
+        match self {
+            AnyBrain::FeedForward(b) => b.feed(inputs, outputs),
+            AnyBrain::Gru(b) => b.feed(inputs, outputs),
+            AnyBrain::Neat(b) => b.feed(inputs, outputs),
+            AnyBrain::Scripted(b) => b.feed(inputs, outputs),
+            AnyBrain::Custom(b) => b.dyn_feed(inputs, outputs),
+        }
+    }
+
+    /// Distance between brains of the same type, infinite between different types.
+    fn genetic_distance(&self, other: &Self) -> f64 {
+        match (self, other) {
+            (AnyBrain::FeedForward(a), AnyBrain::FeedForward(b)) => a.genetic_distance(b),
+            (AnyBrain::Gru(a), AnyBrain::Gru(b)) => a.genetic_distance(b),
+            (AnyBrain::Neat(a), AnyBrain::Neat(b)) => a.genetic_distance(b),
+            (AnyBrain::Scripted(a), AnyBrain::Scripted(b)) => {
+                Brain::<INPUT, OUTPUT>::genetic_distance(a, b)
+            }
+            (AnyBrain::Custom(a), AnyBrain::Custom(b)) => a.dyn_genetic_distance(b.as_ref()),
+            _ => f64::INFINITY,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            AnyBrain::FeedForward(b) => b.size(),
+            AnyBrain::Gru(b) => b.size(),
+            AnyBrain::Neat(b) => b.size(),
+            AnyBrain::Scripted(b) => Brain::<INPUT, OUTPUT>::size(b),
+            AnyBrain::Custom(b) => b.dyn_size(),
+        }
+    }
+
+    /// Only brains of the same type can be combined.
+    fn crossover<R: Rng + ?Sized>(
+        &self,
+        other: &Self,
+        mix: &mut Mixer,
+        rng: &mut R,
+    ) -> Option<Self> {
+        match (self, other) {
+            (AnyBrain::FeedForward(a), AnyBrain::FeedForward(b)) => {
+                a.crossover(b, mix, rng).map(AnyBrain::FeedForward)
+            }
+            (AnyBrain::Gru(a), AnyBrain::Gru(b)) => a.crossover(b, mix, rng).map(AnyBrain::Gru),
+            (AnyBrain::Neat(a), AnyBrain::Neat(b)) => a.crossover(b, mix, rng).map(AnyBrain::Neat),
+            (AnyBrain::Scripted(a), AnyBrain::Scripted(b)) => {
+                Brain::<INPUT, OUTPUT>::crossover(a, b, mix, rng).map(AnyBrain::Scripted)
+            }
+            (AnyBrain::Custom(a), AnyBrain::Custom(b)) => a
+                .dyn_crossover(b.as_ref(), mix, &mut { rng })
+                .map(AnyBrain::Custom),
+            _ => None,
+        }
+    }
+
+    fn set_inputs(&mut self, inputs: &[usize]) {
+        match self {
+            AnyBrain::FeedForward(b) => b.set_inputs(inputs),
+            AnyBrain::Gru(b) => b.set_inputs(inputs),
+            AnyBrain::Neat(b) => b.set_inputs(inputs),
+            AnyBrain::Scripted(b) => Brain::<INPUT, OUTPUT>::set_inputs(b, inputs),
+            AnyBrain::Custom(b) => b.dyn_set_inputs(inputs),
+        }
+    }
+}
+
+/// How the genes of two parents are combined by [`Brain::crossover`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Crossover {
+    /// Each weight comes from either parent at random.
+    #[default]
+    Uniform,
+    /// Each layer, with its biases and activations, comes whole from either parent at random.
+    Layer,
+    /// Each weight is the same random weighted average of both parents' weights.
+    Blend,
+}
+
+/// The random choices of one [`Crossover`], shared by all the genes of one offspring.
+pub struct Mixer {
+    op: Crossover,
+    // Weight of the first parent, 0 or 1 per layer for `Crossover::Layer`.
+    alpha: f64,
+}
+
+impl Mixer {
+    pub fn new<R: Rng + ?Sized>(op: Crossover, rng: &mut R) -> Self {
+        let mut mix = Self {
+            op,
+            alpha: rng.gen(),
+        };
+        mix.next_layer(rng);
+        mix
+    }
+
+    /// Move on to the genes of the next layer.
+    pub fn next_layer<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+        if self.op == Crossover::Layer {
+            self.alpha = if rng.gen_bool(0.5) { 1. } else { 0. };
+        }
+    }
+
+    /// Combine a gene of the first parent, `a`, with the same gene of the second.
+    pub fn gene<R: Rng + ?Sized>(&self, a: f64, b: f64, rng: &mut R) -> f64 {
+        match self.op {
+            Crossover::Uniform => self.pick(a, b, rng),
+            Crossover::Layer | Crossover::Blend => self.alpha * a + (1. - self.alpha) * b,
+        }
+    }
+
+    /// Choose between genes which cannot be averaged.
+    pub fn pick<T, R: Rng + ?Sized>(&self, a: T, b: T, rng: &mut R) -> T {
+        let first = match self.op {
+            Crossover::Uniform => rng.gen_bool(0.5),
+            Crossover::Layer | Crossover::Blend => self.alpha >= 0.5,
+        };
+        if first {
+            a
+        } else {
+            b
+        }
+    }
+
+    /// Combine genes element-wise, `a` and `b` must have the same shape.
+    pub fn genes<D: Dimension, R: Rng + ?Sized>(
+        &self,
+        a: &Array<f64, D>,
+        b: &Array<f64, D>,
+        rng: &mut R,
+    ) -> Array<f64, D> {
+        let mut genes = a.clone();
+        genes.zip_mut_with(b, |x, y| *x = self.gene(*x, *y, rng));
+        genes
+    }
+}
+
+/// Nonlinearity applied to the summed input of a neuron.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Activation {
+    Identity,
+    Tanh,
+    Relu,
+    Sigmoid,
+    Sine,
+    Gaussian,
+}
+
+impl Activation {
+    pub const ALL: [Activation; 6] = [
+        Activation::Identity,
+        Activation::Tanh,
+        Activation::Relu,
+        Activation::Sigmoid,
+        Activation::Sine,
+        Activation::Gaussian,
+    ];
+
+    pub fn apply(self, f: f64) -> f64 {
+        match self {
+            Activation::Identity => f,
+            Activation::Tanh => f.tanh(),
+            Activation::Relu => f.max(0.),
+            Activation::Sigmoid => 1. / (1. + (-f).exp()),
+            Activation::Sine => f.sin(),
+            Activation::Gaussian => (-f * f).exp(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod brain_tests {
+    use super::*;
+    use ndarray_rand::rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn genetic_distance() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let a = NeuralBrain::<4, 2>::new_random(&[3], &[], &mut rng);
+        let b = a.clone_mutate(0.1, &StructureMutation::default(), &mut rng);
+        let c = b.clone_mutate(0.1, &StructureMutation::default(), &mut rng);
+        assert_eq!(a.genetic_distance(&a), 0.);
+        assert_eq!(a.genetic_distance(&b), b.genetic_distance(&a));
+        assert!(a.genetic_distance(&b) > 0.);
+        assert!(a.genetic_distance(&c) > a.genetic_distance(&b));
+    }
+
+    #[test]
+    fn crossover() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let a = NeuralBrain::<4, 2>::new_random(&[3, 3], &[], &mut rng);
+        let b = NeuralBrain::<4, 2>::new_random(&[3, 3], &[], &mut rng);
+
+        let mut mix = Mixer::new(Crossover::Layer, &mut rng);
+        let c = a.crossover(&b, &mut mix, &mut rng).unwrap();
+        c.get_layers().iter().enumerate().for_each(|(l, m)| {
+            assert!(m == a.get_layers()[l] || m == b.get_layers()[l]);
+        });
+
+        let mut mix = Mixer::new(Crossover::Uniform, &mut rng);
+        let c = a.crossover(&b, &mut mix, &mut rng).unwrap();
+        c.get_layers()[0].indexed_iter().for_each(|(i, w)| {
+            assert!(*w == a.get_layers()[0][i] || *w == b.get_layers()[0][i]);
+        });
+        assert!(c.genetic_distance(&a) > 0. && c.genetic_distance(&b) > 0.);
+
+        let mut mix = Mixer::new(Crossover::Blend, &mut rng);
+        let c = a.crossover(&a, &mut mix, &mut rng).unwrap();
+        assert!(c.genetic_distance(&a) < 1e-12);
+
+        let d = NeuralBrain::<4, 2>::new_random(&[3], &[], &mut rng);
+        assert!(a.crossover(&d, &mut mix, &mut rng).is_none());
+    }
+
+    // A hand-written controller.
+    #[derive(Clone)]
+    struct Constant(f64);
+
+    impl Brain<4, 2> for Constant {
+        fn clone_mutate<R: Rng + ?Sized>(
+            &self,
+            std: f64,
+            _: &StructureMutation,
+            rng: &mut R,
+        ) -> Self {
+            Constant(self.0 + rng.gen_range(-std..std))
+        }
+
+        fn feed(&mut self, _: &[f64; 4], outputs: &mut [f64; 2]) {
+            outputs.iter_mut().for_each(|o| *o = self.0);
+        }
+
+        fn genetic_distance(&self, other: &Self) -> f64 {
+            (self.0 - other.0).abs()
+        }
+
+        fn size(&self) -> usize {
+            1
+        }
+
+        fn crossover<R: Rng + ?Sized>(
+            &self,
+            other: &Self,
+            mix: &mut Mixer,
+            rng: &mut R,
+        ) -> Option<Self> {
+            Some(Constant(mix.gene(self.0, other.0, rng)))
+        }
+    }
+
+    #[test]
+    fn custom_brains() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut a = AnyBrain::<4, 2>::custom(Constant(0.5));
+        let ff =
+            AnyBrain::<4, 2>::new_random(BrainKind::FeedForward, &WorldConfig::default(), &mut rng);
+        assert_eq!(a.kind(), None);
+        assert_eq!(a.size(), 1);
+
+        let mut out = [0.; 2];
+        a.feed(&[0.; 4], &mut out);
+        assert_eq!(out, [0.5; 2]);
+
+        let b = a.clone_mutate(0.1, &StructureMutation::default(), &mut rng);
+        assert!(a.genetic_distance(&b) > 0. && a.genetic_distance(&b) < 0.1);
+        assert_eq!(a.genetic_distance(&ff), f64::INFINITY);
+
+        let mut mix = Mixer::new(Crossover::Blend, &mut rng);
+        assert!(a.crossover(&b, &mut mix, &mut rng).is_some());
+        assert!(a.crossover(&ff, &mut mix, &mut rng).is_none());
+    }
+
+    fn outputs(brain: &mut NeuralBrain<4, 2>) -> [f64; 2] {
+        let mut out = [0.; 2];
+        brain.feed(&[0.3, -0.2, 0.1, 0.5], &mut out);
+        out
+    }
+
+    fn assert_consistent(brain: &NeuralBrain<4, 2>) {
+        let layers = brain.get_layers();
+        assert_eq!(layers[0].ncols(), brain.get_inputs().len());
+        assert_eq!(layers.last().unwrap().nrows(), 2);
+        assert!(layers.windows(2).all(|w| w[0].nrows() == w[1].ncols()));
+    }
+
+    #[test]
+    fn growing_keeps_function() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut a = NeuralBrain::<4, 2>::new_random(&[3], &[], &mut rng);
+        let mut b = a.clone();
+        b.grow_layer(0, &mut rng);
+        assert_eq!(b.get_hidden_sizes(), vec![4]);
+        assert_consistent(&b);
+        assert_eq!(outputs(&mut a), outputs(&mut b));
+    }
+
+    #[test]
+    fn inserting_layer_keeps_function() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut a = NeuralBrain::<4, 2>::new_random(&[3], &[], &mut rng);
+        let mut b = a.clone();
+        b.insert_layer(0, 0., &mut rng);
+        assert_eq!(b.get_hidden_sizes(), vec![3, 3]);
+        assert_eq!(b.get_activations()[0], vec![Activation::Identity; 3]);
+        assert_consistent(&b);
+        let (oa, ob) = (outputs(&mut a), outputs(&mut b));
+        assert!(oa.iter().zip(ob.iter()).all(|(a, b)| (a - b).abs() < 1e-12));
+
+        // The inserted layer is linear, so removing it again is exact too.
+        b.remove_layer(0);
+        let ob = outputs(&mut b);
+        assert!(oa.iter().zip(ob.iter()).all(|(a, b)| (a - b).abs() < 1e-12));
+
+        // A layer fed by the output matrix is as wide as the output.
+        b.insert_layer(1, 0., &mut rng);
+        assert_eq!(b.get_hidden_sizes(), vec![3, 2]);
+        assert_consistent(&b);
+        let ob = outputs(&mut b);
+        assert!(oa.iter().zip(ob.iter()).all(|(a, b)| (a - b).abs() < 1e-12));
+        b.remove_layer(1);
+
+        b.shrink_layer(0, 1);
+        assert_eq!(b.get_hidden_sizes(), vec![2]);
+        assert_consistent(&b);
+    }
+
+    #[test]
+    fn biases_act_without_input() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut brain = NeuralBrain::<4, 2>::new_random(&[3], &[Activation::Relu], &mut rng);
+        assert_eq!(brain.get_activations(), &[vec![Activation::Relu; 3]]);
+        let mut out = [0.; 2];
+        brain.feed(&[0.; 4], &mut out);
+        let hidden = brain.get_biases()[0].mapv(|b| b.max(0.));
+        let expected = brain.get_layers()[1].dot(&hidden) + &brain.get_biases()[1];
+        assert!(out
+            .iter()
+            .zip(expected.iter())
+            .all(|(o, e)| (o - e).abs() < 1e-12));
+        assert!(out.iter().any(|o| *o != 0.));
+    }
+
+    #[test]
+    fn structure_evolves() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut brain = NeuralBrain::<4, 2>::new_random(&[3], &[], &mut rng);
+        let start = brain.size();
+        let mut sizes = vec![];
+        (0..2000).for_each(|_| {
+            brain = brain.clone_mutate(0.01, &StructureMutation::default(), &mut rng);
+            assert_consistent(&brain);
+            // Capped by the widest hidden or output layer, not the 4 inputs.
+            assert!(brain.get_hidden_sizes().iter().all(|s| *s <= 3));
+            assert!(outputs(&mut brain).iter().all(|o| o.is_finite()));
+            sizes.push(brain.size());
+        });
+        assert!(sizes.iter().any(|s| *s != start));
+    }
+
+    #[test]
+    fn layer_count_capped() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut brain = NeuralBrain::<4, 2>::new_random(&[3], &[], &mut rng);
+        let structure = StructureMutation {
+            insert_layer_prob: 0.5,
+            remove_layer_prob: 0.,
+            max_hidden_layers: 2,
+            ..StructureMutation::default()
+        };
+        (0..200).for_each(|_| {
+            brain = brain.clone_mutate(0.01, &structure, &mut rng);
+            assert!(brain.get_hidden_sizes().len() <= 2);
+        });
+        assert_eq!(brain.get_hidden_sizes().len(), 2);
+    }
+
+    #[test]
+    fn activations_mutate() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let start = NeuralBrain::<4, 2>::new_random(&[3], &[], &mut rng);
+        let evolve = |change_activation_prob, rng: &mut ChaCha8Rng| {
+            // Inserted layers are linear, keep them out.
+            let structure = StructureMutation {
+                change_activation_prob,
+                insert_layer_prob: 0.,
+                ..StructureMutation::default()
+            };
+            (0..50).fold(start.clone(), |b, _| b.clone_mutate(0.01, &structure, rng))
+        };
+        let changed = |b: &NeuralBrain<4, 2>| {
+            b.get_activations()
+                .iter()
+                .flatten()
+                .any(|a| *a != Activation::Tanh)
+        };
+        assert!(!changed(&evolve(0., &mut rng)));
+        assert!(changed(&evolve(1., &mut rng)));
+    }
+
+    #[test]
+    fn genetic_distance_across_shapes() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let a = NeuralBrain::<4, 2>::new_random(&[3], &[], &mut rng);
+        let b = NeuralBrain::<4, 2>::new_random(&[3, 5], &[], &mut rng);
+        assert!(a.genetic_distance(&b).is_finite());
+        assert_eq!(a.genetic_distance(&b), b.genetic_distance(&a));
+    }
+
+    #[test]
+    fn reads_only_set_inputs() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let a = NeuralBrain::<4, 2>::new_random(&[3], &[], &mut rng);
+        let mut b = a.clone();
+        b.set_inputs(&[0, 2, 3]);
+        assert_consistent(&b);
+        assert_eq!(b.get_layers()[0].ncols(), 3);
+        let feed = |brain: &mut NeuralBrain<4, 2>, second| {
+            let mut out = [0.; 2];
+            brain.feed(&[0.3, second, 0.1, 0.5], &mut out);
+            out
+        };
+        assert_eq!(feed(&mut b, 0.), feed(&mut b, 1.));
+        assert_eq!(feed(&mut b, 0.), feed(&mut a.clone(), 0.));
+
+        // Reading the input again starts without weights.
+        let mut c = b.clone();
+        c.set_inputs(&[0, 1, 2, 3]);
+        assert_consistent(&c);
+        assert_eq!(feed(&mut c, 1.), feed(&mut b, 1.));
+        assert_eq!(c.genetic_distance(&b), 0.);
+        assert!(a.genetic_distance(&b) > 0.);
+
+        let mut mix = Mixer::new(Crossover::Uniform, &mut rng);
+        let d = b.crossover(&a, &mut mix, &mut rng).unwrap();
+        assert_eq!(d.get_inputs(), b.get_inputs());
+        assert_consistent(&d);
+    }
 }
@@ -0,0 +1,584 @@
+use crate::model::brain::{Activation, BrainKind, Crossover, StructureMutation};
+use crate::model::creature::{ActionSelection, Observation, Vision, VisionChannel};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Tunable constants of the simulation.
+///
+/// Every field has a default, so config files only need to list what they change. Load with
+/// [`WorldConfig::from_file`], which validates the values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorldConfig {
+    /// Value of a fully grown grass tile.
+    pub grass_max: u32,
+    /// Grass regrown per tile per tick.
+    pub grass_recharge: u32,
+    /// Fraction (0-1) of a tile's grass eaten in one bite.
+    pub grass_eat_frac: f64,
+    /// Fraction (0-1) of the world covered in rock outcrops, where no grass grows. The terrain is
+    /// drawn from the seed when the world is made.
+    pub terrain_rock: f64,
+
+    /// How close a creature must be to be bitten.
+    pub bite_dist: f64,
+    /// Energy removed from a bitten creature by a pure carnivore.
+    pub bite_damage: u32,
+
+    /// Radians turned per tick.
+    pub turn_speed: f64,
+    /// Distance walked per tick.
+    pub walk_speed: f64,
+
+    /// Energy spent every tick just living.
+    pub creature_energy_cost: u32,
+    /// Additional energy spent on ticks spent walking.
+    pub creature_walk_energy_cost: u32,
+    /// Age at which creatures die.
+    pub max_age: u32,
+
+    /// Number of sites the field of view of spawned creatures is split into, up to
+    /// [`Observation::MAX_SITES`]. Like the rest of the eyes it evolves from there.
+    pub vision_sites: usize,
+    /// Width of the field of view of spawned creatures, in radians.
+    pub vision_range: f64,
+    /// How far spawned creatures see, up to [`Observation::MAX_DIST`].
+    pub vision_dist: f64,
+    /// What each site reports about the creature it sees besides its color and distance, so
+    /// creatures can tell for example predators from grazers, and whether it also sees grass and
+    /// terrain.
+    pub vision_channels: Vec<VisionChannel>,
+    /// Contrast (0-1) between a creature's body color and the grass under it below which others
+    /// do not see it, so colors can evolve towards camouflage. 0 makes every creature visible.
+    pub vision_min_contrast: f64,
+    /// Energy spent every tick per unit of area in view, see [`Vision::upkeep`].
+    pub vision_area_cost: f64,
+    /// Energy spent every tick per site of the eyes.
+    pub vision_site_cost: f64,
+
+    /// Scent laid on its tile each tick by a creature emitting at full strength.
+    pub scent_deposit: f64,
+    /// Energy spent each tick emitting scent at full strength.
+    pub scent_cost: f64,
+    /// Fraction (0-1) of the scent on each tile lost every tick.
+    pub scent_decay: f64,
+    /// Fraction (0-1) of the scent on each tile spread evenly to its four neighbours every tick.
+    pub scent_diffusion: f64,
+
+    /// Energy of spawned and newborn creatures.
+    pub starting_energy: u32,
+    /// Energy a creature needs before it can replicate.
+    pub replicate_min_energy: u32,
+    /// Energy a creature spends on replicating.
+    pub replicate_cost: u32,
+
+    /// How close two creatures must be to mate.
+    pub mate_dist: f64,
+    /// Energy each of two creatures needs before they can mate.
+    pub mate_min_energy: u32,
+    /// Energy each parent spends on mating.
+    pub mate_cost: u32,
+    /// How the parents' brains and traits (diet, color, temperature, eyes and mutation rates) are
+    /// combined when mating.
+    pub crossover: Crossover,
+
+    /// Standard deviation of the noise added to brain weights and traits on replication, for
+    /// spawned creatures. Every creature carries its own rate, which self-adapts in offspring.
+    pub mut_rate: f64,
+    /// Starting rate for the traits (diet, color, temperature and eyes) to evolve separately from
+    /// the brain's rate, `None` to share one rate.
+    pub trait_mut_rate: Option<f64>,
+    /// How fast mutation rates self-adapt: offspring scale each rate by `exp(N(0, this))` before
+    /// mutating, 0 keeps rates fixed.
+    pub mut_rate_adaptation: f64,
+    /// Kind of brain spawned creatures are given, newborns inherit theirs.
+    pub brain: BrainKind,
+    /// Kinds of brain to draw from at random for each spawned creature instead of `brain`, so
+    /// they compete in one world.
+    pub brain_mix: Vec<BrainKind>,
+    /// Hidden layer sizes of new brains.
+    pub brain_hidden_layers: Vec<usize>,
+    /// Initial activation of each hidden layer of new brains, `tanh` for layers without one.
+    pub brain_activations: Vec<Activation>,
+    /// Chance per mutation of connecting two unconnected neurons of a NEAT brain.
+    pub add_connection_prob: f64,
+    /// Chance per mutation of splitting a connection of a NEAT brain with a new neuron.
+    pub add_node_prob: f64,
+    /// Chance per mutation of enabling or disabling a connection of a NEAT brain.
+    pub toggle_connection_prob: f64,
+    /// Chance per mutation of adding a neuron to a hidden layer of a feed-forward brain.
+    pub grow_layer_prob: f64,
+    /// Chance per mutation of removing a neuron from a hidden layer of a feed-forward brain.
+    pub shrink_layer_prob: f64,
+    /// Chance per mutation of inserting a hidden layer into a feed-forward brain.
+    pub insert_layer_prob: f64,
+    /// Chance per mutation of removing a hidden layer from a feed-forward brain.
+    pub remove_layer_prob: f64,
+    /// Most hidden layers a feed-forward brain can evolve.
+    pub max_hidden_layers: usize,
+    /// Chance per mutation of changing the activation of a hidden neuron of a feed-forward or
+    /// NEAT brain.
+    pub change_activation_prob: f64,
+
+    /// How creatures choose actions from their brain outputs.
+    pub action_selection: ActionSelection,
+    /// Temperature of stochastic action selection for spawned creatures, it evolves from there.
+    pub temperature: f64,
+
+    /// Genetic distance from its family's founder beyond which a newborn founds a new family,
+    /// `None` to keep families forever.
+    pub speciation_threshold: Option<f64>,
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        let starting_energy = 4096;
+        Self {
+            grass_max: 512,
+            grass_recharge: 1,
+            grass_eat_frac: 0.5,
+            terrain_rock: 0.,
+            bite_dist: 1.0,
+            bite_damage: starting_energy,
+            turn_speed: 0.01,
+            walk_speed: 0.02,
+            creature_energy_cost: 1,
+            creature_walk_energy_cost: 3,
+            max_age: 60000,
+            vision_sites: 5,
+            vision_range: std::f64::consts::FRAC_PI_2,
+            vision_dist: 10.,
+            vision_channels: vec![],
+            vision_min_contrast: 0.,
+            vision_area_cost: 0.004,
+            vision_site_cost: 0.02,
+            scent_deposit: 1.,
+            scent_cost: 0.5,
+            scent_decay: 0.02,
+            scent_diffusion: 0.1,
+            starting_energy,
+            replicate_min_energy: 4 * starting_energy,
+            replicate_cost: 3 * starting_energy,
+            mate_dist: 1.0,
+            mate_min_energy: 2 * starting_energy,
+            mate_cost: 3 * starting_energy / 2,
+            crossover: Crossover::Uniform,
+            mut_rate: 0.05,
+            trait_mut_rate: None,
+            mut_rate_adaptation: 0.1,
+            brain: BrainKind::FeedForward,
+            brain_mix: vec![],
+            brain_hidden_layers: vec![7],
+            brain_activations: vec![Activation::Tanh],
+            add_connection_prob: 0.05,
+            add_node_prob: 0.03,
+            toggle_connection_prob: 0.02,
+            grow_layer_prob: 0.02,
+            shrink_layer_prob: 0.02,
+            insert_layer_prob: 0.005,
+            remove_layer_prob: 0.005,
+            max_hidden_layers: 8,
+            change_activation_prob: 0.02,
+            action_selection: ActionSelection::Argmax,
+            temperature: 1.0,
+            speciation_threshold: None,
+        }
+    }
+}
+
+/// Why a config could not be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(String),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "config io error: {}", e),
+            ConfigError::Parse(e) => write!(f, "malformed config: {}", e),
+            ConfigError::Invalid(e) => write!(f, "invalid config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl WorldConfig {
+    /// Eyes of spawned creatures.
+    pub fn vision(&self) -> Vision {
+        Vision {
+            sites: self.vision_sites,
+            range: self.vision_range,
+            dist: self.vision_dist,
+        }
+    }
+
+    /// Chances of each change to the structure of mutating brains.
+    pub fn structure_mutation(&self) -> StructureMutation {
+        StructureMutation {
+            add_connection_prob: self.add_connection_prob,
+            add_node_prob: self.add_node_prob,
+            toggle_connection_prob: self.toggle_connection_prob,
+            grow_layer_prob: self.grow_layer_prob,
+            shrink_layer_prob: self.shrink_layer_prob,
+            insert_layer_prob: self.insert_layer_prob,
+            remove_layer_prob: self.remove_layer_prob,
+            max_hidden_layers: self.max_hidden_layers,
+            change_activation_prob: self.change_activation_prob,
+        }
+    }
+
+    /// Load and validate a config, parsed as JSON if the file ends in `.json` and TOML otherwise.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::from_json_str(&contents),
+            _ => Self::from_toml_str(&contents),
+        }
+    }
+
+    /// Parse and validate a TOML config.
+    pub fn from_toml_str(s: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(s).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parse and validate a JSON config.
+    pub fn from_json_str(s: &str) -> Result<Self, ConfigError> {
+        let config: Self =
+            serde_json::from_str(s).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Check that the values describe a world that can be simulated.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        fn check(ok: bool, msg: &str) -> Result<(), ConfigError> {
+            if ok {
+                Ok(())
+            } else {
+                Err(ConfigError::Invalid(msg.to_string()))
+            }
+        }
+
+        check(self.grass_max > 0, "grass_max must be positive")?;
+        check(
+            self.grass_max.checked_add(self.grass_recharge).is_some(),
+            "grass_max + grass_recharge must fit in 32 bits",
+        )?;
+        check(
+            (0. ..=1.).contains(&self.grass_eat_frac),
+            "grass_eat_frac must be between 0 and 1",
+        )?;
+        check(
+            (0. ..=1.).contains(&self.terrain_rock),
+            "terrain_rock must be between 0 and 1",
+        )?;
+        // Targets are looked up in the grid of creatures, which only reaches one step away.
+        check(
+            (0. ..=Observation::MAX_DIST).contains(&self.bite_dist),
+            &format!("bite_dist must be between 0 and {}", Observation::MAX_DIST),
+        )?;
+        check(
+            self.turn_speed.is_finite() && self.turn_speed >= 0.,
+            "turn_speed must be non-negative",
+        )?;
+        check(
+            self.walk_speed.is_finite() && self.walk_speed >= 0.,
+            "walk_speed must be non-negative",
+        )?;
+        check(self.max_age > 0, "max_age must be positive")?;
+        check(
+            self.scent_deposit.is_finite() && self.scent_deposit >= 0.,
+            "scent_deposit must be non-negative",
+        )?;
+        check(
+            self.scent_cost.is_finite() && self.scent_cost >= 0.,
+            "scent_cost must be non-negative",
+        )?;
+        check(
+            (0. ..=1.).contains(&self.scent_decay),
+            "scent_decay must be between 0 and 1",
+        )?;
+        check(
+            (0. ..=1.).contains(&self.scent_diffusion),
+            "scent_diffusion must be between 0 and 1",
+        )?;
+        check(
+            (1..=Observation::MAX_SITES).contains(&self.vision_sites),
+            &format!(
+                "vision_sites must be between 1 and {}",
+                Observation::MAX_SITES
+            ),
+        )?;
+        check(
+            (Vision::MIN_RANGE..=std::f64::consts::TAU).contains(&self.vision_range),
+            &format!(
+                "vision_range must be between {} and a full turn",
+                Vision::MIN_RANGE
+            ),
+        )?;
+        check(
+            (Vision::MIN_DIST..=Observation::MAX_DIST).contains(&self.vision_dist),
+            &format!(
+                "vision_dist must be between {} and {}",
+                Vision::MIN_DIST,
+                Observation::MAX_DIST
+            ),
+        )?;
+        check(
+            (0. ..=1.).contains(&self.vision_min_contrast),
+            "vision_min_contrast must be between 0 and 1",
+        )?;
+        check(
+            self.vision_area_cost.is_finite() && self.vision_area_cost >= 0.,
+            "vision_area_cost must be non-negative",
+        )?;
+        check(
+            self.vision_site_cost.is_finite() && self.vision_site_cost >= 0.,
+            "vision_site_cost must be non-negative",
+        )?;
+        check(self.starting_energy > 0, "starting_energy must be positive")?;
+        check(
+            self.replicate_cost <= self.replicate_min_energy,
+            "replicate_cost must not exceed replicate_min_energy",
+        )?;
+        check(
+            (0. ..=Observation::MAX_DIST).contains(&self.mate_dist),
+            &format!("mate_dist must be between 0 and {}", Observation::MAX_DIST),
+        )?;
+        check(
+            self.mate_cost <= self.mate_min_energy,
+            "mate_cost must not exceed mate_min_energy",
+        )?;
+        check(
+            self.mut_rate.is_finite() && self.mut_rate >= 0.,
+            "mut_rate must be non-negative",
+        )?;
+        check(
+            self.trait_mut_rate
+                .map(|r| r.is_finite() && r >= 0.)
+                .unwrap_or(true),
+            "trait_mut_rate must be non-negative",
+        )?;
+        check(
+            self.mut_rate_adaptation.is_finite() && self.mut_rate_adaptation >= 0.,
+            "mut_rate_adaptation must be non-negative",
+        )?;
+        check(
+            self.brain_hidden_layers.iter().all(|s| *s > 0),
+            "brain_hidden_layers must not contain empty layers",
+        )?;
+        [
+            ("add_connection_prob", self.add_connection_prob),
+            ("add_node_prob", self.add_node_prob),
+            ("toggle_connection_prob", self.toggle_connection_prob),
+            ("grow_layer_prob", self.grow_layer_prob),
+            ("shrink_layer_prob", self.shrink_layer_prob),
+            ("insert_layer_prob", self.insert_layer_prob),
+            ("remove_layer_prob", self.remove_layer_prob),
+            ("change_activation_prob", self.change_activation_prob),
+        ]
+        .iter()
+        .try_for_each(|(name, p)| {
+            check(
+                (0. ..=1.).contains(p),
+                &format!("{} must be between 0 and 1", name),
+            )
+        })?;
+        check(
+            self.temperature.is_finite() && self.temperature > 0.,
+            "temperature must be positive",
+        )?;
+        check(
+            self.speciation_threshold
+                .map(|t| t.is_finite() && t > 0.)
+                .unwrap_or(true),
+            "speciation_threshold must be positive",
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+
+    #[test]
+    fn default_is_valid() {
+        assert!(WorldConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn example_matches_default() {
+        let example = include_str!("../../config.example.toml");
+        assert_eq!(
+            WorldConfig::from_toml_str(example).unwrap(),
+            WorldConfig::default()
+        );
+    }
+
+    #[test]
+    fn partial_toml_keeps_defaults() {
+        let config =
+            WorldConfig::from_toml_str("grass_max = 100\nbrain_hidden_layers = [4, 4]").unwrap();
+        assert_eq!(config.grass_max, 100);
+        assert_eq!(config.brain_hidden_layers, vec![4, 4]);
+        assert_eq!(config.brain_activations, vec![Activation::Tanh]);
+        assert_eq!(config.max_age, WorldConfig::default().max_age);
+    }
+
+    #[test]
+    fn brain_kind() {
+        let config = WorldConfig::from_toml_str("brain = \"gru\"").unwrap();
+        assert_eq!(config.brain, BrainKind::Gru);
+        let config = WorldConfig::from_toml_str("brain_mix = [\"gru\", \"neat\"]").unwrap();
+        assert_eq!(config.brain_mix, vec![BrainKind::Gru, BrainKind::Neat]);
+        assert!(WorldConfig::from_toml_str("brain = \"lstm\"").is_err());
+    }
+
+    #[test]
+    fn structure_mutation() {
+        let config = WorldConfig::from_toml_str("add_node_prob = 0.1").unwrap();
+        assert_eq!(config.structure_mutation().add_node_prob, 0.1);
+        assert!(matches!(
+            WorldConfig::from_toml_str("toggle_connection_prob = 1.5"),
+            Err(ConfigError::Invalid(_))
+        ));
+        let config = WorldConfig::from_toml_str("max_hidden_layers = 2").unwrap();
+        assert_eq!(config.structure_mutation().max_hidden_layers, 2);
+        assert!(matches!(
+            WorldConfig::from_toml_str("insert_layer_prob = -0.1"),
+            Err(ConfigError::Invalid(_))
+        ));
+        let config = WorldConfig::from_toml_str("change_activation_prob = 0.5").unwrap();
+        assert_eq!(config.structure_mutation().change_activation_prob, 0.5);
+        assert!(matches!(
+            WorldConfig::from_toml_str("change_activation_prob = 2.0"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn crossover() {
+        let config = WorldConfig::from_toml_str("crossover = \"blend\"").unwrap();
+        assert_eq!(config.crossover, Crossover::Blend);
+        assert!(matches!(
+            WorldConfig::from_toml_str("mate_cost = 10000\nmate_min_energy = 5000"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn action_selection() {
+        let config = WorldConfig::from_toml_str("action_selection = \"gumbel\"").unwrap();
+        assert_eq!(config.action_selection, ActionSelection::Gumbel);
+        assert!(matches!(
+            WorldConfig::from_toml_str("temperature = 0.0"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn terrain_rock() {
+        let config = WorldConfig::from_toml_str("terrain_rock = 0.2").unwrap();
+        assert_eq!(config.terrain_rock, 0.2);
+        assert!(matches!(
+            WorldConfig::from_toml_str("terrain_rock = 1.5"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn vision_channels() {
+        let config = WorldConfig::from_toml_str("vision_channels = [\"rgb\", \"diet\"]").unwrap();
+        assert_eq!(
+            config.vision_channels,
+            vec![VisionChannel::Rgb, VisionChannel::Diet]
+        );
+        assert!(WorldConfig::from_toml_str("vision_channels = [\"smell\"]").is_err());
+    }
+
+    #[test]
+    fn activations() {
+        let config =
+            WorldConfig::from_toml_str("brain_activations = [\"relu\", \"gaussian\"]").unwrap();
+        assert_eq!(
+            config.brain_activations,
+            vec![Activation::Relu, Activation::Gaussian]
+        );
+    }
+
+    #[test]
+    fn rejects_overflowing_grass() {
+        assert!(WorldConfig::from_toml_str("grass_recharge = 4294967295").is_err());
+        assert!(WorldConfig::from_toml_str("grass_max = 4294967295\ngrass_recharge = 0").is_ok());
+    }
+
+    #[test]
+    fn rejects_reach_beyond_grid_step() {
+        assert!(WorldConfig::from_toml_str("bite_dist = 20.0").is_ok());
+        assert!(matches!(
+            WorldConfig::from_toml_str("bite_dist = 25.0"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            WorldConfig::from_toml_str("mate_dist = 25.0"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn json_config() {
+        let config = WorldConfig::from_json_str(r#"{"walk_speed": 0.5}"#).unwrap();
+        assert_eq!(config.walk_speed, 0.5);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(matches!(
+            WorldConfig::from_toml_str("grass_maximum = 100"),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(matches!(
+            WorldConfig::from_toml_str("grass_eat_frac = 1.5"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            WorldConfig::from_toml_str("brain_hidden_layers = [3, 0]"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            WorldConfig::from_toml_str("speciation_threshold = 0.0"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            WorldConfig::from_toml_str("vision_sites = 10"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            WorldConfig::from_toml_str("vision_dist = 25.0"),
+            Err(ConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            WorldConfig::from_toml_str("vision_range = 0.0"),
+            Err(ConfigError::Invalid(_))
+        ));
+    }
+}
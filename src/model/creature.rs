@@ -4,82 +4,288 @@ use num_traits::FromPrimitive;
 // Contains information about the world as seen at a snapshot.
 
 use crate::model::brain::*;
-use ndarray_rand::rand;
+use crate::model::config::WorldConfig;
+use crate::model::scripted::Script;
+use ndarray_rand::rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+use std::cmp::max;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
-const NUM_SITES: usize = 5;
+const MAX_SITES: usize = 9;
 const MAX_DIST: f64 = 20.;
 const GRASS_NEIGHBORS: usize = 3;
 
+/// A creature's view of the world for a single tick, the inputs to its brain.
+///
+/// Sight is split into [`Observation::MAX_SITES`] slots, of which a creature's [`Vision`] fills
+/// the middle ones, so gaining or losing sites keeps the middle of the field of view on the same
+/// brain inputs. Slots outside the creature's eye read the same as sites which see nothing.
+///
+/// Besides color and distance, each slot reports the channels of
+/// [`WorldConfig::vision_channels`] about the creature it sees. Channels which are off read 0.
+///
+/// Brains only read the slots of the creature's own sites and the channels which are on, see
+/// [`Vision::brain_inputs`], so an eye with fewer sites makes for a smaller brain.
 #[derive(Clone, Debug)]
 pub struct Observation {
-    // Colors and dists (0-1) for 0-MAX_DIST
-    pub colors: [f64; NUM_SITES],
-    pub dists: [f64; NUM_SITES],
+    // Brightness of the body color (0-1) and distances (0-MAX_DIST) of the nearest creature in
+    // each slot, MAX_DIST and color 0 where nothing is seen.
+    pub colors: [f64; MAX_SITES],
+    pub dists: [f64; MAX_SITES],
+    // Body color (0-1 each) of the creature seen, see Creature::get_body_color.
+    pub rgb: [[f64; 3]; MAX_SITES],
+    // Fraction (0-1) of a half turn the creature seen takes up.
+    pub sizes: [f64; MAX_SITES],
+    // Heading of the creature seen relative to our own, from -1 to 1 for a half turn clockwise
+    // to a half turn counter-clockwise.
+    pub headings: [f64; MAX_SITES],
+    // Vegetable efficiency of the creature seen.
+    pub diets: [f64; MAX_SITES],
+    // Mean grass (0-1) along a ray through the middle of the site, out to the view distance.
+    pub grass: [f64; MAX_SITES],
+    // Fraction (0-1) of rock along the same ray.
+    pub terrain: [f64; MAX_SITES],
     pub neighboring_grass: [f64; GRASS_NEIGHBORS * GRASS_NEIGHBORS],
     pub energy: f64,
+    // Scent (0-1, saturating) on our tile and its gradient, forward and to the left.
+    pub scent: f64,
+    pub scent_gradient: [f64; 2],
 }
 
 impl Observation {
-    pub const NUM_SITES: usize = NUM_SITES;
+    /// Most sites an eye can have.
+    pub const MAX_SITES: usize = MAX_SITES;
+    /// Furthest an eye can see.
     pub const MAX_DIST: f64 = MAX_DIST;
-    pub const VISION_RANGE: f64 = std::f64::consts::FRAC_PI_2;
     pub const GRASS_NEIGHBORS: usize = GRASS_NEIGHBORS;
     pub const NUM_NEIGHBORS: usize = Self::GRASS_NEIGHBORS * Self::GRASS_NEIGHBORS;
 
-    pub const NUM_INPUTS: usize = 2 * Self::NUM_SITES + Self::NUM_NEIGHBORS + 1;
+    /// Values per slot: color, distance, red, green, blue, size, heading, diet, grass and
+    /// terrain.
+    pub const SLOT_INPUTS: usize = 10;
 
+    pub const NUM_INPUTS: usize = Self::SLOT_INPUTS * Self::MAX_SITES + Self::NUM_NEIGHBORS + 4;
+
+    /// An observation in which nothing is seen.
     pub fn new_empty() -> Self {
         Self {
-            colors: [0.; Self::NUM_SITES],
-            dists: [std::f64::INFINITY; Self::NUM_SITES],
+            colors: [0.; Self::MAX_SITES],
+            dists: [f64::INFINITY; Self::MAX_SITES],
+            rgb: [[0.; 3]; Self::MAX_SITES],
+            sizes: [0.; Self::MAX_SITES],
+            headings: [0.; Self::MAX_SITES],
+            diets: [0.; Self::MAX_SITES],
+            grass: [0.; Self::MAX_SITES],
+            terrain: [0.; Self::MAX_SITES],
             neighboring_grass: [0.; Self::NUM_NEIGHBORS],
             energy: 0.0,
+            scent: 0.0,
+            scent_gradient: [0.; 2],
         }
     }
 
+    /// Index into `neighboring_grass` of the tile at offset `(ix, iy)` from the top left.
     pub fn neighbor_index(ix: usize, iy: usize) -> usize {
         iy * Self::GRASS_NEIGHBORS + ix
     }
 
+    /// Flatten into the brain input layout: each channel for every slot in turn, then the
+    /// grass, energy and scent.
     pub fn inputs(&self) -> [f64; Self::NUM_INPUTS] {
         let mut inputs = [0.; Self::NUM_INPUTS];
-        (0..Self::NUM_SITES).for_each(|i| {
-            inputs[i] = self.colors[i];
-        });
-        (0..Self::NUM_SITES).for_each(|i| {
-            inputs[Self::NUM_SITES + i] = self.dists[i];
-        });
-        (0..Self::GRASS_NEIGHBORS * Self::GRASS_NEIGHBORS).for_each(|i| {
-            inputs[2 * Self::NUM_SITES + i] = self.neighboring_grass[i];
-        });
-        inputs[2 * Self::NUM_SITES + Self::NUM_NEIGHBORS] = self.energy;
+        let (colors, rest) = inputs.split_at_mut(Self::MAX_SITES);
+        let (dists, rest) = rest.split_at_mut(Self::MAX_SITES);
+        let (rgb, rest) = rest.split_at_mut(3 * Self::MAX_SITES);
+        let (sizes, rest) = rest.split_at_mut(Self::MAX_SITES);
+        let (headings, rest) = rest.split_at_mut(Self::MAX_SITES);
+        let (diets, rest) = rest.split_at_mut(Self::MAX_SITES);
+        let (grass_rays, rest) = rest.split_at_mut(Self::MAX_SITES);
+        let (terrain, rest) = rest.split_at_mut(Self::MAX_SITES);
+        let (grass, rest) = rest.split_at_mut(Self::NUM_NEIGHBORS);
+        colors.copy_from_slice(&self.colors);
+        dists.copy_from_slice(&self.dists);
+        rgb.chunks_exact_mut(3)
+            .zip(self.rgb.iter())
+            .for_each(|(v, c)| v.copy_from_slice(c));
+        sizes.copy_from_slice(&self.sizes);
+        headings.copy_from_slice(&self.headings);
+        diets.copy_from_slice(&self.diets);
+        grass_rays.copy_from_slice(&self.grass);
+        terrain.copy_from_slice(&self.terrain);
+        grass.copy_from_slice(&self.neighboring_grass);
+        rest[0] = self.energy;
+        rest[1] = self.scent;
+        rest[2..].copy_from_slice(&self.scent_gradient);
 
         inputs
     }
+
+    /// Read back an observation from brain inputs laid out by [`Observation::inputs`], anything
+    /// after them is ignored.
+    pub fn from_inputs(inputs: &[f64]) -> Self {
+        let mut o = Self::new_empty();
+        let (colors, rest) = inputs.split_at(Self::MAX_SITES);
+        let (dists, rest) = rest.split_at(Self::MAX_SITES);
+        let (rgb, rest) = rest.split_at(3 * Self::MAX_SITES);
+        let (sizes, rest) = rest.split_at(Self::MAX_SITES);
+        let (headings, rest) = rest.split_at(Self::MAX_SITES);
+        let (diets, rest) = rest.split_at(Self::MAX_SITES);
+        let (grass_rays, rest) = rest.split_at(Self::MAX_SITES);
+        let (terrain, rest) = rest.split_at(Self::MAX_SITES);
+        let (grass, rest) = rest.split_at(Self::NUM_NEIGHBORS);
+        o.colors.copy_from_slice(colors);
+        o.dists.copy_from_slice(dists);
+        o.rgb
+            .iter_mut()
+            .zip(rgb.chunks_exact(3))
+            .for_each(|(c, v)| c.copy_from_slice(v));
+        o.sizes.copy_from_slice(sizes);
+        o.headings.copy_from_slice(headings);
+        o.diets.copy_from_slice(diets);
+        o.grass.copy_from_slice(grass_rays);
+        o.terrain.copy_from_slice(terrain);
+        o.neighboring_grass.copy_from_slice(grass);
+        o.energy = rest[0];
+        o.scent = rest[1];
+        o.scent_gradient.copy_from_slice(&rest[2..4]);
+        o
+    }
+}
+
+/// Optional values each site of the eyes reports about the creature it sees, see
+/// [`WorldConfig::vision_channels`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VisionChannel {
+    /// Body color as red, green and blue, see [`Creature::get_body_color`].
+    Rgb,
+    /// Apparent size, growing with the energy of the creature seen and shrinking with distance.
+    Size,
+    /// Heading of the creature seen relative to the observer's.
+    Heading,
+    /// Vegetable efficiency of the creature seen, telling grazers from predators.
+    Diet,
+    /// Mean grass along a ray through the middle of each site, whether or not a creature is seen,
+    /// so herbivores can find distant pastures.
+    Grass,
+    /// Fraction of [rock](crate::model::Terrain::Rock) along the same ray, telling bare rock from
+    /// grazed meadow.
+    Terrain,
+}
+
+/// The heritable shape of a creature's eyes.
+///
+/// The field of view is centred on the creature's heading and split into `sites` equally wide
+/// sites, each seeing the nearest creature within `dist`. Larger eyes cost more energy to keep,
+/// see [`Vision::upkeep`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vision {
+    /// Number of sites, from 1 to [`Observation::MAX_SITES`].
+    pub sites: usize,
+    /// Width of the field of view in radians, from [`Vision::MIN_RANGE`] up to a full turn.
+    pub range: f64,
+    /// How far creatures are seen, from [`Vision::MIN_DIST`] up to [`Observation::MAX_DIST`].
+    pub dist: f64,
+}
+
+impl Vision {
+    /// Narrowest field of view eyes can evolve, in radians.
+    pub const MIN_RANGE: f64 = 0.05;
+    /// Shortest view distance eyes can evolve.
+    pub const MIN_DIST: f64 = 0.5;
+
+    /// Slots of an [`Observation`] these sites fill, from the clockwise edge of the field of
+    /// view to the counter-clockwise one.
+    pub fn slots(&self) -> std::ops::Range<usize> {
+        let first = (Observation::MAX_SITES - self.sites) / 2;
+        first..first + self.sites
+    }
+
+    /// Slot seeing the direction `dtheta` radians counter-clockwise from the clockwise edge of the
+    /// field of view, which must be within it.
+    pub fn slot(&self, dtheta: f64) -> usize {
+        let site = (self.sites as f64 * dtheta / self.range).floor() as usize;
+        self.slots().start + site.min(self.sites - 1)
+    }
+
+    /// Width of each site in radians.
+    pub fn site_width(&self) -> f64 {
+        self.range / self.sites as f64
+    }
+
+    /// Direction of the middle of the `i`th site, in radians counter-clockwise from the heading.
+    pub fn site_angle(&self, i: usize) -> f64 {
+        (i as f64 + 0.5) * self.site_width() - self.range / 2.
+    }
+
+    /// Indices of the brain inputs, laid out by [`Creature::brain_inputs`], which these eyes fill
+    /// in when seeing `channels`: the slots of their sites in color, distance and each channel
+    /// which is on, then everything besides sight.
+    ///
+    /// Brains are set to read only these, see [`Brain::set_inputs`].
+    pub fn brain_inputs(&self, channels: &[VisionChannel]) -> Vec<usize> {
+        let on = |c| channels.contains(&c);
+        // Whether each block of slot inputs is read and its values per slot, in the order of
+        // Observation::inputs.
+        let blocks = [
+            (true, 1),
+            (true, 1),
+            (on(VisionChannel::Rgb), 3),
+            (on(VisionChannel::Size), 1),
+            (on(VisionChannel::Heading), 1),
+            (on(VisionChannel::Diet), 1),
+            (on(VisionChannel::Grass), 1),
+            (on(VisionChannel::Terrain), 1),
+        ];
+        let mut indices = vec![];
+        let mut start = 0;
+        blocks.iter().for_each(|(read, width)| {
+            if *read {
+                indices.extend(self.slots().flat_map(|slot| {
+                    let first = start + slot * width;
+                    first..first + width
+                }));
+            }
+            start += width * Observation::MAX_SITES;
+        });
+        indices.extend(start..Creature::NUM_BRAIN_INPUTS);
+        indices
+    }
+
+    /// Energy spent every tick on these eyes: `vision_area_cost` per unit of area in view plus
+    /// `vision_site_cost` per site.
+    pub fn upkeep(&self, config: &WorldConfig) -> f64 {
+        let area = self.range * self.dist * self.dist / 2.;
+        config.vision_area_cost * area + config.vision_site_cost * self.sites as f64
+    }
 }
 
-#[derive(FromPrimitive, Debug)]
+/// Which way to turn this tick.
+#[derive(FromPrimitive, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TurningAction {
     WAIT,
     LEFT,
     RIGHT,
 }
 
-#[derive(FromPrimitive, Debug)]
+/// Whether to walk forward this tick.
+#[derive(FromPrimitive, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum MovementAction {
     WAIT,
     FORWARD,
 }
 
-#[derive(FromPrimitive, Debug)]
+/// What to do with the surroundings this tick.
+#[derive(FromPrimitive, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Action {
     WAIT,
     EAT,
     BITE,
     REPLICATE,
+    MATE,
 }
 
 impl TurningAction {
@@ -89,89 +295,281 @@ impl MovementAction {
     pub const NUM_ACTIONS: usize = 2;
 }
 impl Action {
-    pub const NUM_ACTIONS: usize = 4;
+    pub const NUM_ACTIONS: usize = 5;
+}
+
+/// How a creature turns the outputs of its brain into actions.
+///
+/// The stochastic modes sample with the creature's own evolving temperature: near zero they
+/// approach argmax, large temperatures approach uniformly random actions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionSelection {
+    /// Always the action with the highest output.
+    #[default]
+    Argmax,
+    /// Each action with probability proportional to `exp(output / temperature)`.
+    Softmax,
+    /// The highest output after adding Gumbel noise scaled by the temperature, which follows the
+    /// same distribution as softmax.
+    Gumbel,
 }
 
 const TOTAL_ACTIONS: usize =
     TurningAction::NUM_ACTIONS + MovementAction::NUM_ACTIONS + Action::NUM_ACTIONS;
 
+/// Red, green and blue (0-1 each) color of a family, from the lowest three bytes of its hash.
+pub fn fam_color(fam: usize) -> [f64; 3] {
+    let mut s = DefaultHasher::new();
+    fam.hash(&mut s);
+    let h = s.finish();
+    [0, 8, 16].map(|shift| ((h >> shift) & 255) as f64 / 255.)
+}
+
+// The heritable traits besides the brain.
+#[derive(Clone, Copy)]
+struct Traits {
+    veg_eff: f64,
+    color: [f64; 3],
+    temperature: f64,
+    brain_mut_rate: f64,
+    trait_mut_rate: f64,
+    vision: Vision,
+}
+
+/// The brain of a creature, of the kind chosen by [`WorldConfig::brain`].
+pub type CreatureBrain = AnyBrain<{ Creature::NUM_BRAIN_INPUTS }, { Creature::NUM_BRAIN_OUTPUTS }>;
+
+/// A single creature, its body state and the brain driving it.
+#[derive(Serialize, Deserialize)]
 pub struct Creature {
     id: usize,
     fam: usize,
+    parent: Option<usize>,
+    other_parent: Option<usize>,
+    generation: u32,
+    birth_tick: u64,
     x: f64,
     y: f64,
     theta: f64,
-    color: f64,
+    // Red, green and blue (0-1 each), heritable.
+    color: [f64; 3],
+    #[serde(skip)]
     last_obs: Option<Observation>,
     energy: u32,
+    // Id of whoever bit away the last of our energy.
+    killed_by: Option<usize>,
     // Vegetable efficiency
     veg_eff: f64,
+    // Of stochastic action selection.
+    temperature: f64,
+    // Standard deviations of the noise offspring are mutated with.
+    brain_mut_rate: f64,
+    trait_mut_rate: f64,
+    vision: Vision,
+    // Fraction of an energy unit of upkeep owed, paid once it adds up to a whole unit.
+    upkeep_debt: f64,
+    // Strength (0-1) of the scent chosen to be laid this tick.
+    #[serde(skip)]
+    emission: f64,
     age: u32,
     // For brain stuff
-    brain: NeuralBrain<{ Creature::NUM_BRAIN_INPUTS }, { Creature::NUM_BRAIN_OUTPUTS }>,
-    input_buff: [f64; Creature::NUM_BRAIN_INPUTS],
+    brain: CreatureBrain,
     mem: [f64; Creature::MEM_SIZE],
+    // Own random stream so draws don't depend on the order creatures are processed in.
+    rng: ChaCha8Rng,
 }
 
 impl Creature {
     pub const NUM_BRAIN_INPUTS: usize = { Observation::NUM_INPUTS + Creature::MEM_SIZE };
-    pub const NUM_BRAIN_OUTPUTS: usize = { TOTAL_ACTIONS + Creature::MEM_SIZE };
+    pub const NUM_BRAIN_OUTPUTS: usize = { TOTAL_ACTIONS + 1 + Creature::MEM_SIZE };
     pub const MEM_SIZE: usize = 3;
-    pub const STARTING_ENERGY: u32 = 4096;
-    pub const MUT_RATE: f64 = 0.05;
-
-    pub fn new(id: usize, fam: usize, x: f64, y: f64, theta: f64, veg: f64) -> Self {
-        let mut s = DefaultHasher::new();
-        fam.hash(&mut s);
-        let hash = s.finish();
-        let c = hash as f64 / std::u64::MAX as f64;
 
+    /// A new creature with a random brain, founding or joining family `fam` and colored like it.
+    ///
+    /// `pos` is `(x, y, theta)` and `veg` is the efficiency (0-1) with which it digests grass, the
+    /// rest goes to meat, unless the brain follows a [`Script`] which has a diet of its own. The
+    /// brain, of a kind drawn from `config.brain_mix` if set, and the creature's own random stream
+    /// are drawn from `rng`. The brain only reads the inputs of its eyes, see
+    /// [`Vision::brain_inputs`].
+    pub fn new<R: Rng + ?Sized>(
+        id: usize,
+        fam: usize,
+        pos: (f64, f64, f64),
+        veg: f64,
+        tick: u64,
+        config: &WorldConfig,
+        rng: &mut R,
+    ) -> Self {
+        let (x, y, theta) = pos;
+        let kind = match config.brain_mix.len() {
+            0 => config.brain,
+            n => config.brain_mix[rng.gen_range(0..n)],
+        };
+        let vision = config.vision();
+        let mut brain = CreatureBrain::new_random(kind, config, rng);
+        brain.set_inputs(&vision.brain_inputs(&config.vision_channels));
+        let veg = brain.script().map_or(veg, Script::veg_eff);
         Self {
             id,
             fam,
+            parent: None,
+            other_parent: None,
+            generation: 0,
+            birth_tick: tick,
             x,
             y,
             theta,
-            color: c,
+            color: fam_color(fam),
             last_obs: None,
-            energy: Self::STARTING_ENERGY,
+            energy: config.starting_energy,
+            killed_by: None,
             veg_eff: veg,
-            brain: NeuralBrain::default(),
-            input_buff: [0.0; Creature::NUM_BRAIN_INPUTS],
+            temperature: config.temperature,
+            brain_mut_rate: config.mut_rate,
+            trait_mut_rate: config.trait_mut_rate.unwrap_or(config.mut_rate),
+            vision,
+            upkeep_debt: 0.,
+            emission: 0.,
+            brain,
             age: 0,
             mem: [0.0; Creature::MEM_SIZE],
+            rng: ChaCha8Rng::seed_from_u64(rng.gen()),
         }
     }
 
-    pub fn clone_mutate(&self, new_id: usize) -> Self {
-        let newbrain = self.brain.clone_mutate(Self::MUT_RATE);
+    /// The same creature driven by `brain` instead, for example a hand-written controller in an
+    /// [`AnyBrain::Custom`].
+    pub fn with_brain(mut self, brain: CreatureBrain) -> Self {
+        self.brain = brain;
+        self
+    }
 
-        // Tweak veg mut between 0 and 1
-        let veg_logit = ((1. / self.veg_eff) - 1.).ln();
+    /// A mutated offspring with id `new_id`, born at `tick` at the same position, drawing from this
+    /// creature's random stream.
+    ///
+    /// The mutation rates self-adapt first, and the offspring is mutated with its new rates. Its
+    /// brain then reads the inputs of its own eyes, new ones starting without weights.
+    pub fn clone_mutate(&mut self, new_id: usize, tick: u64, config: &WorldConfig) -> Self {
+        let traits = self.mutate_traits(self.traits(), config);
+        let newbrain = self.brain.clone_mutate(
+            traits.brain_mut_rate,
+            &config.structure_mutation(),
+            &mut self.rng,
+        );
+        self.offspring(newbrain, traits, new_id, tick, config)
+    }
 
-        let mut rng = rand::thread_rng();
-        let normal = Normal::new(0., Self::MUT_RATE).unwrap();
-        let v = normal.sample(&mut rng);
-        let veg_logit = veg_logit + v;
-        let new_veg_eff = 1. / (1. + veg_logit.exp());
+    /// An offspring of this creature and `other`, like [`Creature::clone_mutate`] but with brain,
+    /// diet, color, temperature, eyes and mutation rates combined from both parents with
+    /// `config.crossover` before mutating.
+    ///
+    /// The offspring joins this creature's family. `None` if their brains cannot be combined,
+    /// see [`Brain::crossover`].
+    pub fn mate(
+        &mut self,
+        other: &Creature,
+        new_id: usize,
+        tick: u64,
+        config: &WorldConfig,
+    ) -> Option<Self> {
+        let mut mix = Mixer::new(config.crossover, &mut self.rng);
+        let brain = self
+            .brain
+            .crossover(&other.brain, &mut mix, &mut self.rng)?;
+        let (a, b) = (self.traits(), other.traits());
+        let rng = &mut self.rng;
+        let mixed = Traits {
+            veg_eff: mix.gene(a.veg_eff, b.veg_eff, rng),
+            color: [0, 1, 2].map(|i| mix.gene(a.color[i], b.color[i], rng)),
+            temperature: mix.gene(a.temperature, b.temperature, rng),
+            brain_mut_rate: mix.gene(a.brain_mut_rate, b.brain_mut_rate, rng),
+            trait_mut_rate: mix.gene(a.trait_mut_rate, b.trait_mut_rate, rng),
+            vision: Vision {
+                sites: mix.pick(a.vision.sites, b.vision.sites, rng),
+                range: mix.gene(a.vision.range, b.vision.range, rng),
+                dist: mix.gene(a.vision.dist, b.vision.dist, rng),
+            },
+        };
+
+        let traits = self.mutate_traits(mixed, config);
+        let structure = config.structure_mutation();
+        let newbrain = brain.clone_mutate(traits.brain_mut_rate, &structure, &mut self.rng);
+        let mut child = self.offspring(newbrain, traits, new_id, tick, config);
+        child.other_parent = Some(other.id);
+        child.generation = max(self.generation, other.generation) + 1;
+        Some(child)
+    }
+
+    fn traits(&self) -> Traits {
+        Traits {
+            veg_eff: self.veg_eff,
+            color: self.color,
+            temperature: self.temperature,
+            brain_mut_rate: self.brain_mut_rate,
+            trait_mut_rate: self.trait_mut_rate,
+            vision: self.vision,
+        }
+    }
 
+    // Self-adapt the mutation rates, as in evolution strategies, then mutate the other traits at
+    // the new trait rate.
+    fn mutate_traits(&mut self, t: Traits, config: &WorldConfig) -> Traits {
+        let rng = &mut self.rng;
+        let adapt = Normal::new(0., config.mut_rate_adaptation).unwrap();
+        let brain_mut_rate = t.brain_mut_rate * adapt.sample(rng).exp();
+        let trait_mut_rate = match config.trait_mut_rate {
+            Some(_) => t.trait_mut_rate * adapt.sample(rng).exp(),
+            None => brain_mut_rate,
+        };
+        Traits {
+            veg_eff: mutate_veg_eff(t.veg_eff, trait_mut_rate, rng),
+            color: mutate_color(t.color, trait_mut_rate, rng),
+            temperature: mutate_temperature(t.temperature, trait_mut_rate, rng),
+            vision: mutate_vision(t.vision, trait_mut_rate, rng),
+            brain_mut_rate,
+            trait_mut_rate,
+        }
+    }
+
+    fn offspring(
+        &mut self,
+        mut newbrain: CreatureBrain,
+        traits: Traits,
+        new_id: usize,
+        tick: u64,
+        config: &WorldConfig,
+    ) -> Self {
+        newbrain.set_inputs(&traits.vision.brain_inputs(&config.vision_channels));
         Self {
             id: new_id,
             fam: self.fam,
+            parent: Some(self.id),
+            other_parent: None,
+            generation: self.generation + 1,
+            birth_tick: tick,
             x: self.x,
             y: self.y,
             theta: self.theta,
-            color: self.color,
+            color: traits.color,
             last_obs: None,
-            energy: Self::STARTING_ENERGY,
-            veg_eff: new_veg_eff,
+            energy: config.starting_energy,
+            killed_by: None,
+            veg_eff: newbrain.script().map_or(traits.veg_eff, Script::veg_eff),
+            temperature: traits.temperature,
+            brain_mut_rate: traits.brain_mut_rate,
+            trait_mut_rate: traits.trait_mut_rate,
+            vision: traits.vision,
+            upkeep_debt: 0.,
+            emission: 0.,
             age: 0,
             brain: newbrain,
-            input_buff: [0.0; Creature::NUM_BRAIN_INPUTS],
             mem: [0.0; Creature::MEM_SIZE],
+            rng: ChaCha8Rng::seed_from_u64(self.rng.gen()),
         }
     }
 
+    /// Ticks lived.
     pub fn get_age(&self) -> u32 {
         self.age
     }
@@ -181,21 +579,72 @@ impl Creature {
         self.age
     }
 
+    /// Efficiency (0-1) of digesting grass, meat is digested at `1 - veg_eff`.
     pub fn get_veg_eff(&self) -> f64 {
         self.veg_eff
     }
 
+    /// Temperature of stochastic [`ActionSelection`], evolving in log space.
+    pub fn get_temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Standard deviation of the noise added to the brains of offspring.
+    pub fn get_brain_mut_rate(&self) -> f64 {
+        self.brain_mut_rate
+    }
+
+    /// Standard deviation of the noise added to the diet, color, temperature and eyes of
+    /// offspring.
+    pub fn get_trait_mut_rate(&self) -> f64 {
+        self.trait_mut_rate
+    }
+
+    /// Shape of the eyes this creature observes the world with.
+    pub fn get_vision(&self) -> Vision {
+        self.vision
+    }
+
     pub fn get_id(&self) -> usize {
         self.id
     }
+
+    /// Id of the founding ancestor.
     pub fn get_fam(&self) -> usize {
         self.fam
     }
 
+    /// Move to family `fam`, keeping the body color.
+    pub fn set_fam(&mut self, fam: usize) {
+        self.fam = fam;
+    }
+
+    /// Id of the creature this one was cloned from, `None` for founders.
+    pub fn get_parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    /// Id of the creature the parent mated with, `None` unless born of mating.
+    pub fn get_other_parent(&self) -> Option<usize> {
+        self.other_parent
+    }
+
+    /// Length of the longest line of ancestors back to a founder.
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// World tick at which this creature was born or spawned.
+    pub fn get_birth_tick(&self) -> u64 {
+        self.birth_tick
+    }
+
+    /// The observation used to choose the last action, if any.
     pub fn get_last_observation(&self) -> Option<&Observation> {
         self.last_obs.as_ref()
     }
 
+    /// Position and heading `(x, y, theta)`.
     pub fn get_pos(&self) -> (f64, f64, f64) {
         (self.x, self.y, self.theta)
     }
@@ -211,38 +660,101 @@ impl Creature {
         }
     }
 
+    /// Brightness (0-1) of the body color, which is all other creatures see of it unless they
+    /// see in [color](VisionChannel::Rgb).
     pub fn get_color(&self) -> f64 {
+        let [r, g, b] = self.color;
+        0.299 * r + 0.587 * g + 0.114 * b
+    }
+
+    /// Red, green and blue (0-1 each) body color. Founders take their family's color, see
+    /// [`fam_color`], and it mutates in offspring like the diet.
+    pub fn get_body_color(&self) -> [f64; 3] {
         self.color
     }
 
+    /// The same creature with body color `color` instead.
+    pub fn with_body_color(mut self, color: [f64; 3]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Body radius, half a tile at the starting energy and growing with the square root of
+    /// energy.
+    pub fn get_radius(&self, config: &WorldConfig) -> f64 {
+        0.5 * (self.energy as f64 / config.starting_energy as f64).sqrt()
+    }
+
+    /// The brain choosing this creature's actions.
+    pub fn get_brain(&self) -> &CreatureBrain {
+        &self.brain
+    }
+
+    pub(crate) fn get_brain_mut(&mut self) -> &mut CreatureBrain {
+        &mut self.brain
+    }
+
+    /// Memory registers fed back into the brain each tick.
+    pub fn get_mem(&self) -> &[f64; Creature::MEM_SIZE] {
+        &self.mem
+    }
+
+    /// Feed `o` through the brain and choose this tick's actions, updating memory.
+    ///
+    /// See [`BrainBatch`](crate::model::BrainBatch) to do this for many creatures at once.
     pub fn get_preferred_action(
         &mut self,
         o: Observation,
+        selection: ActionSelection,
     ) -> (TurningAction, MovementAction, Action) {
-        // Get all inputs starting at 0 (up to 1 for most, above for others like energy).
-        self.input_buff
-            .iter_mut()
-            .zip(o.inputs().iter().cloned().chain(self.mem.iter().cloned()))
-            .for_each(|(b, v)| *b = v);
+        let mut inputs = [0.; Creature::NUM_BRAIN_INPUTS];
+        self.brain_inputs(&o, &mut inputs);
 
         let mut outputs = [0.; Creature::NUM_BRAIN_OUTPUTS];
+        self.brain.feed(&inputs, &mut outputs);
+        self.act(o, &outputs, selection)
+    }
+
+    /// Write the brain inputs for `o`, the observation followed by memory, to `inputs`.
+    pub fn brain_inputs(&self, o: &Observation, inputs: &mut [f64]) {
+        // Get all inputs starting at 0 (up to 1 for most, above for others like energy).
+        let (obs, mem) = inputs.split_at_mut(Observation::NUM_INPUTS);
+        obs.copy_from_slice(&o.inputs());
+        mem.copy_from_slice(&self.mem);
+    }
 
-        self.brain.feed(&self.input_buff, &mut outputs);
+    /// Choose this tick's actions and scent from the brain's `outputs` for observation `o`,
+    /// updating memory.
+    ///
+    /// Stochastic selection draws from the creature's own random stream. Creatures driven by a
+    /// [`ScriptedBrain`] always take the actions it scores highest.
+    pub fn act(
+        &mut self,
+        o: Observation,
+        outputs: &[f64],
+        selection: ActionSelection,
+    ) -> (TurningAction, MovementAction, Action) {
         let actions = &outputs[0..TOTAL_ACTIONS];
+        let selection = match self.brain {
+            AnyBrain::Scripted(_) => ActionSelection::Argmax,
+            _ => selection,
+        };
 
         let i = TurningAction::NUM_ACTIONS;
-        let (turning_index, _) = max_index(f64::MIN, actions[0..i].iter().cloned());
+        let turning_index = self.select(&actions[0..i], selection);
         let j = TurningAction::NUM_ACTIONS + MovementAction::NUM_ACTIONS;
-        let (moving_index, _) = max_index(f64::MIN, actions[i..j].iter().cloned());
+        let moving_index = self.select(&actions[i..j], selection);
         let k = TOTAL_ACTIONS;
-        let (action_index, _) = max_index(f64::MIN, actions[j..k].iter().cloned());
+        let action_index = self.select(&actions[j..k], selection);
 
         let turn_action = TurningAction::from_usize(turning_index).unwrap();
         let move_action = MovementAction::from_usize(moving_index).unwrap();
         let action = Action::from_usize(action_index).unwrap();
 
+        self.emission = outputs[TOTAL_ACTIONS].tanh().max(0.);
+
         // Set mem
-        let newmem = &outputs[TOTAL_ACTIONS..];
+        let newmem = &outputs[TOTAL_ACTIONS + 1..];
         self.mem
             .iter_mut()
             .zip(newmem.iter())
@@ -252,14 +764,83 @@ impl Creature {
         (turn_action, move_action, action)
     }
 
+    /// Index of the chosen one of `outputs`.
+    fn select(&mut self, outputs: &[f64], selection: ActionSelection) -> usize {
+        let t = self.temperature;
+        match selection {
+            ActionSelection::Argmax => max_index(f64::MIN, outputs.iter().cloned()).0,
+            ActionSelection::Softmax => {
+                let max = outputs.iter().cloned().fold(f64::MIN, f64::max);
+                let weight = |o: f64| ((o - max) / t).exp();
+                let total = outputs.iter().map(|o| weight(*o)).sum::<f64>();
+                // Infinite or NaN outputs leave nothing to sample from.
+                if !(total.is_finite() && total > 0.) {
+                    return max_index(f64::MIN, outputs.iter().cloned()).0;
+                }
+                let mut u = self.rng.gen_range(0. ..total);
+                outputs
+                    .iter()
+                    .position(|o| {
+                        u -= weight(*o);
+                        u < 0.
+                    })
+                    .unwrap_or(outputs.len() - 1)
+            }
+            ActionSelection::Gumbel => {
+                let rng = &mut self.rng;
+                let noisy = outputs.iter().map(|o| {
+                    let u: f64 = rng.gen();
+                    o - t * (-u.ln()).ln()
+                });
+                max_index(f64::MIN, noisy).0
+            }
+        }
+    }
+
+    /// Remaining energy, the creature dies when it reaches zero.
     pub fn get_energy(&self) -> u32 {
         self.energy
     }
 
     pub fn add_energy(&mut self, energy: u32) {
         self.energy += energy;
+        if self.energy > 0 {
+            self.killed_by = None;
+        }
+    }
+
+    /// Id of the creature which bit away the last of this one's energy, if it hasn't eaten since.
+    pub fn get_killed_by(&self) -> Option<usize> {
+        self.killed_by
+    }
+
+    /// Record that `id` bit away the last of this creature's energy.
+    pub fn set_killed_by(&mut self, id: usize) {
+        self.killed_by = Some(id);
+    }
+
+    /// Pay for living one tick: `base` energy plus the upkeep of the eyes, see [`Vision::upkeep`].
+    ///
+    /// Fractions of a unit of upkeep are carried over until they add up to a whole one.
+    pub fn metabolize(&mut self, base: u32, config: &WorldConfig) {
+        self.upkeep_debt += self.vision.upkeep(config);
+        let owed = self.upkeep_debt.floor();
+        self.upkeep_debt -= owed;
+        self.remove_energy(base + owed as u32);
     }
 
+    /// Strength (0-1) of the scent this creature chose to lay this tick.
+    pub fn get_emission(&self) -> f64 {
+        self.emission
+    }
+
+    /// Owe `energy` more, paid along with the upkeep of later ticks, see
+    /// [`Creature::metabolize`].
+    pub fn add_upkeep(&mut self, energy: f64) {
+        self.upkeep_debt += energy;
+    }
+
+    /// Remove up to `energy`, returning the amount actually removed.
     pub fn remove_energy(&mut self, energy: u32) -> u32 {
         if self.energy > energy {
             self.energy -= energy;
@@ -272,6 +853,49 @@ impl Creature {
     }
 }
 
+// Perturb the diet in logit space, so it stays between 0 and 1.
+fn mutate_veg_eff<R: Rng + ?Sized>(veg_eff: f64, std: f64, rng: &mut R) -> f64 {
+    let veg_logit = ((1. / veg_eff) - 1.).ln();
+
+    let normal = Normal::new(0., std).unwrap();
+    let v = normal.sample(rng);
+    let veg_logit = veg_logit + v;
+    1. / (1. + veg_logit.exp())
+}
+
+// Add normal noise to each channel, within 0 and 1.
+fn mutate_color<R: Rng + ?Sized>(color: [f64; 3], std: f64, rng: &mut R) -> [f64; 3] {
+    let normal = Normal::new(0., std).unwrap();
+    color.map(|c| (c + normal.sample(rng)).clamp(0., 1.))
+}
+
+// Scale the temperature by log-normal noise, so it stays positive.
+fn mutate_temperature<R: Rng + ?Sized>(temperature: f64, std: f64, rng: &mut R) -> f64 {
+    let normal = Normal::new(0., std).unwrap();
+    temperature * normal.sample(rng).exp()
+}
+
+// Scale the field of view and view distance by log-normal noise within their limits, and with
+// probability `std` gain or lose a site.
+fn mutate_vision<R: Rng + ?Sized>(vision: Vision, std: f64, rng: &mut R) -> Vision {
+    let normal = Normal::new(0., std).unwrap();
+    let range = vision.range * normal.sample(rng).exp();
+    let dist = vision.dist * normal.sample(rng).exp();
+    let mut sites = vision.sites;
+    if rng.gen_bool(std.min(1.)) {
+        sites = if rng.gen_bool(0.5) {
+            sites + 1
+        } else {
+            sites.saturating_sub(1)
+        };
+    }
+    Vision {
+        sites: sites.clamp(1, Observation::MAX_SITES),
+        range: range.clamp(Vision::MIN_RANGE, std::f64::consts::TAU),
+        dist: dist.clamp(Vision::MIN_DIST, Observation::MAX_DIST),
+    }
+}
+
 fn max_index<It: IntoIterator<Item = T>, T: PartialOrd + Copy>(init: T, it: It) -> (usize, T) {
     it.into_iter()
         .enumerate()
@@ -283,3 +907,106 @@ fn max_index<It: IntoIterator<Item = T>, T: PartialOrd + Copy>(init: T, it: It)
             }
         })
 }
+
+#[cfg(test)]
+mod creature_tests {
+    use super::*;
+
+    #[test]
+    fn softmax_survives_non_finite_outputs() {
+        let config = WorldConfig::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut c = Creature::new(0, 0, (0., 0., 0.), 0.5, 0, &config, &mut rng);
+        let mut outputs = [0.; Creature::NUM_BRAIN_OUTPUTS];
+        outputs[TurningAction::LEFT as usize] = f64::INFINITY;
+        let j = TurningAction::NUM_ACTIONS;
+        outputs[j + MovementAction::WAIT as usize] = f64::NAN;
+        outputs[j + MovementAction::FORWARD as usize] = 1.;
+        let (turn, movement, _) =
+            c.act(Observation::new_empty(), &outputs, ActionSelection::Softmax);
+        assert_eq!(turn, TurningAction::LEFT);
+        assert_eq!(movement, MovementAction::FORWARD);
+    }
+
+    #[test]
+    fn scripted_ignores_action_selection() {
+        let config = WorldConfig {
+            brain: BrainKind::Predator,
+            ..WorldConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut c = Creature::new(0, 0, (0., 0., 0.), 0.5, 0, &config, &mut rng);
+        // Nothing in sight, the predator circles.
+        let mut o = Observation::new_empty();
+        o.dists.iter_mut().for_each(|d| *d = Observation::MAX_DIST);
+        (0..100).for_each(|_| {
+            assert_eq!(
+                c.get_preferred_action(o.clone(), ActionSelection::Softmax),
+                (TurningAction::LEFT, MovementAction::FORWARD, Action::WAIT)
+            );
+        });
+    }
+
+    #[test]
+    fn scripts_keep_their_diet() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        [(BrainKind::Grazer, 1.), (BrainKind::Predator, 0.)]
+            .iter()
+            .for_each(|(brain, veg_eff)| {
+                let config = WorldConfig {
+                    brain: *brain,
+                    ..WorldConfig::default()
+                };
+                let mut c = Creature::new(0, 0, (0., 0., 0.), 0.5, 0, &config, &mut rng);
+                assert_eq!(c.get_veg_eff(), *veg_eff);
+                let child = c.clone_mutate(1, 0, &config);
+                assert_eq!(child.get_veg_eff(), *veg_eff);
+            });
+    }
+
+    #[test]
+    fn vision_stays_positive() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut vision = WorldConfig::default().vision();
+        (0..1000).for_each(|_| {
+            vision = mutate_vision(vision, 2., &mut rng);
+            assert!(vision.range >= Vision::MIN_RANGE && vision.site_width() > 0.);
+            assert!(vision.dist >= Vision::MIN_DIST);
+        });
+    }
+
+    #[test]
+    fn brain_reads_own_sites() {
+        let config = WorldConfig {
+            vision_sites: 3,
+            vision_channels: vec![VisionChannel::Grass],
+            ..WorldConfig::default()
+        };
+        let vision = config.vision();
+        let inputs = vision.brain_inputs(&config.vision_channels);
+        let m = Observation::MAX_SITES;
+        assert_eq!(&inputs[..3], &[3, 4, 5]);
+        assert_eq!(&inputs[3..6], &[m + 3, m + 4, m + 5]);
+        // Grass, the last block of slots but terrain.
+        assert_eq!(inputs[6], (Observation::SLOT_INPUTS - 2) * m + 3);
+        assert_eq!(
+            inputs.len(),
+            3 * 3 + Creature::NUM_BRAIN_INPUTS - Observation::SLOT_INPUTS * m
+        );
+
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut c = Creature::new(0, 0, (0., 0., 0.), 0.5, 0, &config, &mut rng);
+        match c.get_brain() {
+            AnyBrain::FeedForward(b) => assert_eq!(b.get_inputs(), &inputs[..]),
+            _ => unreachable!(),
+        }
+        let child = c.clone_mutate(1, 0, &config);
+        match child.get_brain() {
+            AnyBrain::FeedForward(b) => assert_eq!(
+                b.get_inputs(),
+                &child.get_vision().brain_inputs(&config.vision_channels)[..]
+            ),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use crate::model::stats::DeathCause;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+
+/// Something that happened to a creature during a [`World::update`](crate::model::World::update).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub tick: u64,
+    pub kind: EventKind,
+}
+
+/// What happened, positions are `(x, y)` in grass tiles.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EventKind {
+    /// A creature was born, or spawned by the world if `parent` is `None`. `other_parent` is
+    /// the creature `parent` mated with, if any.
+    Birth {
+        id: usize,
+        parent: Option<usize>,
+        other_parent: Option<usize>,
+        fam: usize,
+        pos: (f64, f64),
+    },
+    /// A creature was removed from the world, `killer` bit away its last energy.
+    Death {
+        id: usize,
+        age: u32,
+        cause: DeathCause,
+        killer: Option<usize>,
+        pos: (f64, f64),
+    },
+    /// A creature ate `grass` from its tile, gaining `energy`.
+    Meal {
+        id: usize,
+        grass: u32,
+        energy: u32,
+        pos: (f64, f64),
+    },
+    /// `attacker` bit `damage` energy from `victim`, gaining `energy`.
+    Bite {
+        attacker: usize,
+        victim: usize,
+        damage: u32,
+        energy: u32,
+        pos: (f64, f64),
+    },
+}
+
+/// Receives the events of every update, in the order they happened.
+///
+/// Subscribe with [`World::subscribe`](crate::model::World::subscribe).
+pub trait EventSink: Send {
+    fn handle(&mut self, event: &Event);
+
+    /// Push out anything buffered, reporting errors held back since the last flush.
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<F: FnMut(&Event) + Send> EventSink for F {
+    fn handle(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+/// Forwards events to a receiver, ignoring them once it has hung up.
+impl EventSink for Sender<Event> {
+    fn handle(&mut self, event: &Event) {
+        let _ = self.send(event.clone());
+    }
+}
+
+/// Encoding of an [`EventLog`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventFormat {
+    /// One JSON object per line.
+    JsonLines,
+    /// Consecutive bincode encoded events, read back with [`read_binary_events`].
+    Binary,
+}
+
+/// Writes events to a file for later analysis.
+pub struct EventLog<W: Write> {
+    out: W,
+    format: EventFormat,
+    error: Option<std::io::Error>,
+}
+
+impl EventLog<BufWriter<File>> {
+    /// Create (or truncate) the log file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P, format: EventFormat) -> std::io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?), format))
+    }
+
+    /// Open the log file at `path` to add events after those already in it.
+    pub fn append<P: AsRef<Path>>(path: P, format: EventFormat) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file), format))
+    }
+}
+
+impl<W: Write> EventLog<W> {
+    pub fn new(out: W, format: EventFormat) -> Self {
+        Self {
+            out,
+            format,
+            error: None,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn write(&mut self, event: &Event) -> std::io::Result<()> {
+        match self.format {
+            EventFormat::JsonLines => {
+                serde_json::to_writer(&mut self.out, event)?;
+                self.out.write_all(b"\n")
+            }
+            EventFormat::Binary => bincode::serialize_into(&mut self.out, event)
+                .map_err(|e| std::io::Error::other(e.to_string())),
+        }
+    }
+}
+
+impl<W: Write + Send> EventSink for EventLog<W> {
+    fn handle(&mut self, event: &Event) {
+        if self.error.is_none() {
+            self.error = self.write(event).err();
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.out.flush(),
+        }
+    }
+}
+
+/// Read every event of a [`EventFormat::Binary`] log.
+pub fn read_binary_events<R: Read>(r: R) -> Result<Vec<Event>, bincode::Error> {
+    let mut r = BufReader::new(r);
+    let mut events = vec![];
+    loop {
+        match bincode::deserialize_from(&mut r) {
+            Ok(event) => events.push(event),
+            Err(e) => match *e {
+                bincode::ErrorKind::Io(ref io)
+                    if io.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(events)
+                }
+                _ => return Err(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod events_tests {
+    use super::*;
+
+    fn example() -> Vec<Event> {
+        vec![
+            Event {
+                tick: 1,
+                kind: EventKind::Birth {
+                    id: 3,
+                    parent: Some(1),
+                    other_parent: None,
+                    fam: 1,
+                    pos: (0.5, 2.),
+                },
+            },
+            Event {
+                tick: 2,
+                kind: EventKind::Death {
+                    id: 1,
+                    age: 10,
+                    cause: DeathCause::Predation,
+                    killer: Some(2),
+                    pos: (1., 1.),
+                },
+            },
+        ]
+    }
+
+    fn log(format: EventFormat) -> Vec<u8> {
+        let mut log = EventLog::new(vec![], format);
+        example().iter().for_each(|e| log.handle(e));
+        log.flush().unwrap();
+        log.into_inner()
+    }
+
+    #[test]
+    fn binary_roundtrip() {
+        let bytes = log(EventFormat::Binary);
+        assert_eq!(read_binary_events(bytes.as_slice()).unwrap(), example());
+    }
+
+    #[test]
+    fn json_lines() {
+        let bytes = log(EventFormat::JsonLines);
+        let events = String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str::<Event>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(events, example());
+    }
+
+    #[test]
+    fn append_keeps_earlier_events() {
+        let path = std::env::temp_dir().join(format!("rcw_events_{}.bin", std::process::id()));
+        let mut log = EventLog::create(&path, EventFormat::Binary).unwrap();
+        example().iter().for_each(|e| log.handle(e));
+        log.flush().unwrap();
+        let mut log = EventLog::append(&path, EventFormat::Binary).unwrap();
+        example().iter().for_each(|e| log.handle(e));
+        log.flush().unwrap();
+
+        let events = read_binary_events(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(events, [example(), example()].concat());
+    }
+}
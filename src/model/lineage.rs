@@ -0,0 +1,261 @@
+use crate::model::creature::Creature;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A creature's place in the family tree.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LineageNode {
+    pub id: usize,
+    /// `None` for founders spawned by the world.
+    pub parent: Option<usize>,
+    /// The creature `parent` mated with, if born of mating.
+    pub other_parent: Option<usize>,
+    pub fam: usize,
+    pub generation: u32,
+    pub birth_tick: u64,
+    /// `None` while alive.
+    pub death_tick: Option<u64>,
+    /// Children with living descendants, in order of birth.
+    pub children: Vec<usize>,
+    /// Children with living descendants this creature is the other parent of.
+    pub other_children: Vec<usize>,
+}
+
+impl LineageNode {
+    pub fn of(c: &Creature) -> Self {
+        Self {
+            id: c.get_id(),
+            parent: c.get_parent(),
+            other_parent: c.get_other_parent(),
+            fam: c.get_fam(),
+            generation: c.get_generation(),
+            birth_tick: c.get_birth_tick(),
+            death_tick: None,
+            children: vec![],
+            other_children: vec![],
+        }
+    }
+}
+
+/// Family tree of every living creature and all of its ancestors.
+///
+/// Dead creatures are forgotten as soon as they have no living descendants, so the store only
+/// holds branches which are not yet extinct. Creatures born of mating hang off their `parent` in
+/// the tree, but keep their other parent alive in the store too.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Lineage {
+    nodes: BTreeMap<usize, LineageNode>,
+}
+
+impl Lineage {
+    /// Add a newly born or spawned creature, its parents must still be in the tree.
+    pub fn insert(&mut self, node: LineageNode) {
+        if let Some(parent) = node.parent.and_then(|p| self.nodes.get_mut(&p)) {
+            parent.children.push(node.id);
+        }
+        if let Some(other) = node.other_parent.and_then(|p| self.nodes.get_mut(&p)) {
+            other.other_children.push(node.id);
+        }
+        self.nodes.insert(node.id, node);
+    }
+
+    /// Mark `id` as dead at `tick`, pruning any ancestors left without living descendants.
+    pub fn record_death(&mut self, id: usize, tick: u64) {
+        if let Some(node) = self.nodes.get_mut(&id) {
+            node.death_tick = Some(tick);
+        }
+        self.prune_from(id);
+    }
+
+    fn prune_from(&mut self, id: usize) {
+        let mut next = vec![id];
+        while let Some(id) = next.pop() {
+            let extinct = match self.nodes.get(&id) {
+                Some(node) => {
+                    node.death_tick.is_some()
+                        && node.children.is_empty()
+                        && node.other_children.is_empty()
+                }
+                None => false,
+            };
+            if extinct {
+                let node = self.nodes.remove(&id).unwrap();
+                if let Some(parent) = node.parent.and_then(|p| self.nodes.get_mut(&p)) {
+                    parent.children.retain(|c| *c != id);
+                    next.push(parent.id);
+                }
+                if let Some(other) = node.other_parent.and_then(|p| self.nodes.get_mut(&p)) {
+                    other.other_children.retain(|c| *c != id);
+                    next.push(other.id);
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&LineageNode> {
+        self.nodes.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Nodes without a known parent, one per surviving tree.
+    pub fn roots(&self) -> impl Iterator<Item = &LineageNode> {
+        self.nodes.values().filter(move |n| {
+            n.parent
+                .map(|p| !self.nodes.contains_key(&p))
+                .unwrap_or(true)
+        })
+    }
+
+    /// Ids from `id`'s parent back to its founder.
+    pub fn ancestors(&self, id: usize) -> Vec<usize> {
+        let mut ancestors = vec![];
+        let mut next = self.nodes.get(&id).and_then(|n| n.parent);
+        while let Some(node) = next.and_then(|p| self.nodes.get(&p)) {
+            ancestors.push(node.id);
+            next = node.parent;
+        }
+        ancestors
+    }
+
+    /// Write the surviving trees in Newick format, one tree per line.
+    ///
+    /// Nodes are labelled by creature id and placed at their birth, so branch lengths are the
+    /// ticks between a parent's and a child's birth.
+    pub fn to_newick(&self) -> String {
+        enum Frame {
+            Open(usize),
+            Close(usize),
+            Comma,
+        }
+
+        let mut out = String::new();
+        for root in self.roots() {
+            let mut stack = vec![Frame::Open(root.id)];
+            while let Some(frame) = stack.pop() {
+                match frame {
+                    Frame::Open(id) => {
+                        let node = &self.nodes[&id];
+                        if node.children.is_empty() {
+                            self.write_label(&mut out, node);
+                        } else {
+                            out.push('(');
+                            stack.push(Frame::Close(id));
+                            node.children.iter().rev().enumerate().for_each(|(i, c)| {
+                                if i > 0 {
+                                    stack.push(Frame::Comma);
+                                }
+                                stack.push(Frame::Open(*c));
+                            });
+                        }
+                    }
+                    Frame::Close(id) => {
+                        out.push(')');
+                        self.write_label(&mut out, &self.nodes[&id]);
+                    }
+                    Frame::Comma => out.push(','),
+                }
+            }
+            out.push_str(";\n");
+        }
+        out
+    }
+
+    fn write_label(&self, out: &mut String, node: &LineageNode) {
+        let length = node
+            .parent
+            .and_then(|p| self.nodes.get(&p))
+            .map(|p| node.birth_tick - p.birth_tick)
+            .unwrap_or(0);
+        out.push_str(&format!("{}:{}", node.id, length));
+    }
+}
+
+#[cfg(test)]
+mod lineage_tests {
+    use super::*;
+
+    fn node(id: usize, parent: Option<usize>, birth_tick: u64) -> LineageNode {
+        LineageNode {
+            id,
+            parent,
+            other_parent: None,
+            fam: 0,
+            generation: 0,
+            birth_tick,
+            death_tick: None,
+            children: vec![],
+            other_children: vec![],
+        }
+    }
+
+    fn example() -> Lineage {
+        let mut l = Lineage::default();
+        l.insert(node(0, None, 0));
+        l.insert(node(1, Some(0), 10));
+        l.insert(node(2, Some(0), 15));
+        l.insert(node(3, Some(1), 30));
+        l.insert(node(4, None, 5));
+        l
+    }
+
+    #[test]
+    fn newick() {
+        assert_eq!(example().to_newick(), "((3:20)1:10,2:15)0:0;\n4:0;\n");
+    }
+
+    #[test]
+    fn ancestors() {
+        assert_eq!(example().ancestors(3), vec![1, 0]);
+        assert!(example().ancestors(4).is_empty());
+    }
+
+    #[test]
+    fn dead_ancestors_of_living_are_kept() {
+        let mut l = example();
+        l.record_death(0, 40);
+        l.record_death(1, 40);
+        assert_eq!(l.len(), 5);
+        assert_eq!(l.get(1).unwrap().death_tick, Some(40));
+    }
+
+    #[test]
+    fn extinct_branches_are_pruned() {
+        let mut l = example();
+        l.record_death(0, 40);
+        l.record_death(1, 40);
+        l.record_death(3, 50);
+        assert!(l.get(3).is_none());
+        assert!(l.get(1).is_none());
+        assert_eq!(l.get(0).unwrap().children, vec![2]);
+        assert_eq!(l.to_newick(), "(2:15)0:0;\n4:0;\n");
+
+        l.record_death(2, 60);
+        l.record_death(4, 60);
+        assert!(l.is_empty());
+    }
+
+    #[test]
+    fn other_parents_are_kept() {
+        let mut l = example();
+        l.insert(LineageNode {
+            other_parent: Some(4),
+            ..node(5, Some(2), 70)
+        });
+        assert_eq!(l.get(4).unwrap().other_children, vec![5]);
+        l.record_death(2, 80);
+        l.record_death(4, 80);
+        assert!(l.get(4).is_some());
+        assert_eq!(l.ancestors(5), vec![2, 0]);
+
+        l.record_death(5, 90);
+        assert!(l.get(4).is_none());
+        assert!(l.get(2).is_none());
+    }
+}
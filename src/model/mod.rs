@@ -1,7 +1,31 @@
+//! The simulation: the world, its creatures and their brains.
+
+pub mod batch;
 pub mod brain;
+pub mod config;
 pub mod creature;
+pub mod events;
+pub mod lineage;
+pub mod neat;
+pub mod recurrent;
+pub mod scripted;
+pub mod snapshot;
+pub mod species;
+pub mod stats;
+pub mod terrain;
 pub mod world;
 
+pub use batch::*;
 pub use brain::*;
+pub use config::*;
 pub use creature::*;
+pub use events::*;
+pub use lineage::*;
+pub use neat::*;
+pub use recurrent::*;
+pub use scripted::*;
+pub use snapshot::*;
+pub use species::*;
+pub use stats::*;
+pub use terrain::*;
 pub use world::*;
@@ -0,0 +1,577 @@
+use crate::model::brain::{Activation, Brain, Mixer, StructureMutation};
+use ndarray_rand::rand::Rng;
+use ndarray_rand::rand_distr::{Distribution, Normal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// Set on every hidden node id, so they never clash with the input and output ids.
+const HIDDEN_BIT: u64 = 1 << 63;
+const LAYER_TAG: u64 = 1;
+const SPLIT_TAG: u64 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeKind {
+    Input,
+    Output,
+    Hidden,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeGene {
+    /// Inputs are numbered from 0, outputs follow, hidden nodes are derived from their origin.
+    pub id: u64,
+    pub kind: NodeKind,
+    pub activation: Activation,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConnectionGene {
+    /// Historical marking, identical for the same connection in every genome, see [`innovation`].
+    pub innovation: u64,
+    pub from: u64,
+    pub to: u64,
+    pub weight: f64,
+    pub enabled: bool,
+}
+
+/// The graph a [`NeatBrain`] is built from.
+///
+/// Connections are kept sorted by innovation number and never form a cycle, disabled ones
+/// included.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NeatGenome {
+    /// Inputs read in increasing order first, then outputs, then hidden nodes in order of
+    /// creation.
+    pub nodes: Vec<NodeGene>,
+    pub connections: Vec<ConnectionGene>,
+}
+
+/// Innovation number of the connection from node `from` to node `to`.
+///
+/// Instead of a global counter handing out numbers as mutations happen, the number is derived
+/// from the connection's end points (and hidden node ids from the connection they split), so the
+/// same structure gets the same number in every lineage and creatures can evolve in parallel.
+pub fn innovation(from: u64, to: u64) -> u64 {
+    mix(from, to)
+}
+
+fn mix(a: u64, b: u64) -> u64 {
+    // splitmix64 finalizer over both words.
+    let mut z = a.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ b;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl NeatGenome {
+    fn has_node(&self, id: u64) -> bool {
+        self.nodes.iter().any(|n| n.id == id)
+    }
+
+    fn add_connection(&mut self, from: u64, to: u64, weight: f64) {
+        let innovation = innovation(from, to);
+        let at = self
+            .connections
+            .partition_point(|c| c.innovation < innovation);
+        self.connections.insert(
+            at,
+            ConnectionGene {
+                innovation,
+                from,
+                to,
+                weight,
+                enabled: true,
+            },
+        );
+    }
+
+    /// Whether `to` can be reached from `from` following connections.
+    fn reaches(&self, from: u64, to: u64) -> bool {
+        let mut stack = vec![from];
+        let mut seen = vec![];
+        while let Some(n) = stack.pop() {
+            if n == to {
+                return true;
+            }
+            if !seen.contains(&n) {
+                seen.push(n);
+                stack.extend(
+                    self.connections
+                        .iter()
+                        .filter(|c| c.from == n)
+                        .map(|c| c.to),
+                );
+            }
+        }
+        false
+    }
+
+    fn mutate<R: Rng + ?Sized>(&mut self, std: f64, structure: &StructureMutation, rng: &mut R) {
+        let d = Normal::new(0., std).unwrap();
+        self.connections
+            .iter_mut()
+            .for_each(|c| c.weight += d.sample(rng));
+
+        if rng.gen_bool(structure.add_connection_prob) {
+            let from = &self.nodes[rng.gen_range(0..self.nodes.len())];
+            let to = &self.nodes[rng.gen_range(0..self.nodes.len())];
+            let (from, to) = (from.clone(), to.clone());
+            let connected = self
+                .connections
+                .iter()
+                .any(|c| c.from == from.id && c.to == to.id);
+            if from.kind != NodeKind::Output
+                && to.kind != NodeKind::Input
+                && !connected
+                && !self.reaches(to.id, from.id)
+            {
+                self.add_connection(from.id, to.id, Normal::new(0., 1.).unwrap().sample(rng));
+            }
+        }
+
+        if rng.gen_bool(structure.add_node_prob) && !self.connections.is_empty() {
+            let i = rng.gen_range(0..self.connections.len());
+            let c = self.connections[i].clone();
+            let id = mix(c.innovation, SPLIT_TAG) | HIDDEN_BIT;
+            if c.enabled && !self.has_node(id) {
+                self.connections[i].enabled = false;
+                self.nodes.push(NodeGene {
+                    id,
+                    kind: NodeKind::Hidden,
+                    activation: Activation::Tanh,
+                });
+                self.add_connection(c.from, id, 1.);
+                self.add_connection(id, c.to, c.weight);
+            }
+        }
+
+        if rng.gen_bool(structure.toggle_connection_prob) && !self.connections.is_empty() {
+            let i = rng.gen_range(0..self.connections.len());
+            self.connections[i].enabled = !self.connections[i].enabled;
+        }
+
+        if rng.gen_bool(structure.change_activation_prob) {
+            let i = rng.gen_range(0..self.nodes.len());
+            if self.nodes[i].kind == NodeKind::Hidden {
+                self.nodes[i].activation = Activation::ALL[rng.gen_range(0..Activation::ALL.len())];
+            }
+        }
+    }
+}
+
+/// A brain whose network topology evolves along with its weights, after NEAT.
+///
+/// Mutation perturbs every weight and occasionally adds a connection, splits a connection with a
+/// new neuron, toggles a connection or changes a neuron's activation. The network is acyclic and
+/// evaluated in topological order, outputs are linear.
+///
+/// Serialized as its [`NeatGenome`], the evaluation order is rebuilt on load.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "NeatGenome", into = "NeatGenome")]
+pub struct NeatBrain<const INPUT: usize, const OUTPUT: usize> {
+    genome: NeatGenome,
+    // Number of input nodes, at the start of the genome's nodes.
+    inputs: usize,
+    // Node indices in evaluation order, with the enabled incoming connections of each.
+    order: Vec<(usize, Vec<(usize, f64)>)>,
+    values: Vec<f64>,
+}
+
+impl<const INPUT: usize, const OUTPUT: usize> From<NeatGenome> for NeatBrain<INPUT, OUTPUT> {
+    fn from(genome: NeatGenome) -> Self {
+        let index = genome
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (n.id, i))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut incoming = vec![vec![]; genome.nodes.len()];
+        let mut pending = vec![0; genome.nodes.len()];
+        genome.connections.iter().for_each(|c| {
+            pending[index[&c.to]] += 1;
+            if c.enabled {
+                incoming[index[&c.to]].push((index[&c.from], c.weight));
+            }
+        });
+
+        // Kahn's algorithm, taking ready nodes in the order they appear in the genome.
+        let mut order = vec![];
+        let mut ready = (0..genome.nodes.len())
+            .filter(|i| pending[*i] == 0)
+            .collect::<Vec<_>>();
+        ready.reverse();
+        while let Some(i) = ready.pop() {
+            if genome.nodes[i].kind != NodeKind::Input {
+                order.push((i, std::mem::take(&mut incoming[i])));
+            }
+            let id = genome.nodes[i].id;
+            let mut next = vec![];
+            genome
+                .connections
+                .iter()
+                .filter(|c| c.from == id)
+                .for_each(|c| {
+                    let j = index[&c.to];
+                    pending[j] -= 1;
+                    if pending[j] == 0 {
+                        next.push(j);
+                    }
+                });
+            next.sort_unstable_by(|a, b| b.cmp(a));
+            ready.extend(next);
+        }
+
+        let inputs = genome
+            .nodes
+            .iter()
+            .take_while(|n| n.kind == NodeKind::Input)
+            .count();
+        let values = vec![0.; genome.nodes.len()];
+        Self {
+            genome,
+            inputs,
+            order,
+            values,
+        }
+    }
+}
+
+impl<const INPUT: usize, const OUTPUT: usize> From<NeatBrain<INPUT, OUTPUT>> for NeatGenome {
+    fn from(brain: NeatBrain<INPUT, OUTPUT>) -> Self {
+        brain.genome
+    }
+}
+
+impl<const INPUT: usize, const OUTPUT: usize> NeatBrain<INPUT, OUTPUT> {
+    /// Fully connected layers of the given sizes with random normal weights, the same starting
+    /// topology as a [`NeuralBrain`](crate::model::NeuralBrain), reading every input.
+    ///
+    /// Hidden layer `i` uses `activations[i]`, or `tanh` if there are fewer activations than
+    /// layers.
+    pub fn new_random<R: Rng + ?Sized>(
+        shape: &[usize],
+        activations: &[Activation],
+        rng: &mut R,
+    ) -> Self {
+        let d = Normal::new(0., 1.).unwrap();
+        let mut genome = NeatGenome {
+            nodes: vec![],
+            connections: vec![],
+        };
+        let node = |id, kind, activation| NodeGene {
+            id,
+            kind,
+            activation,
+        };
+        genome
+            .nodes
+            .extend((0..INPUT as u64).map(|id| node(id, NodeKind::Input, Activation::Identity)));
+        let outputs = (INPUT as u64..(INPUT + OUTPUT) as u64).collect::<Vec<_>>();
+        genome.nodes.extend(
+            outputs
+                .iter()
+                .map(|id| node(*id, NodeKind::Output, Activation::Identity)),
+        );
+
+        let mut last = (0..INPUT as u64).collect::<Vec<_>>();
+        let layers = shape.iter().enumerate().map(|(l, size)| {
+            (0..*size as u64)
+                .map(|i| mix(mix(LAYER_TAG, l as u64), i) | HIDDEN_BIT)
+                .collect::<Vec<_>>()
+        });
+        layers
+            .chain(std::iter::once(outputs))
+            .enumerate()
+            .for_each(|(l, layer)| {
+                let activation = activations.get(l).copied().unwrap_or(Activation::Tanh);
+                layer.iter().for_each(|to| {
+                    if *to & HIDDEN_BIT != 0 {
+                        genome.nodes.push(node(*to, NodeKind::Hidden, activation));
+                    }
+                    last.iter()
+                        .for_each(|from| genome.add_connection(*from, *to, d.sample(rng)));
+                });
+                last = layer;
+            });
+
+        genome.into()
+    }
+
+    pub fn get_genome(&self) -> &NeatGenome {
+        &self.genome
+    }
+}
+
+impl<const INPUT: usize, const OUTPUT: usize> Brain<INPUT, OUTPUT> for NeatBrain<INPUT, OUTPUT> {
+    fn clone_mutate<R: Rng + ?Sized>(
+        &self,
+        std: f64,
+        structure: &StructureMutation,
+        rng: &mut R,
+    ) -> Self {
+        let mut genome = self.genome.clone();
+        genome.mutate(std, structure, rng);
+        genome.into()
+    }
+
+    fn feed(&mut self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]) {
+        let values = &mut self.values;
+        let nodes = &self.genome.nodes;
+        let n = self.inputs;
+        values[..n]
+            .iter_mut()
+            .zip(nodes.iter())
+            .for_each(|(v, node)| *v = inputs[node.id as usize]);
+        self.order.iter().for_each(|(i, incoming)| {
+            let sum = incoming.iter().map(|(j, w)| w * values[*j]).sum();
+            values[*i] = nodes[*i].activation.apply(sum);
+        });
+        outputs.copy_from_slice(&values[n..n + OUTPUT]);
+    }
+
+    /// Number of enabled connections.
+    fn size(&self) -> usize {
+        self.genome.connections.iter().filter(|c| c.enabled).count()
+    }
+
+    /// NEAT compatibility distance: the fraction of connections without a match in the other
+    /// genome plus the mean weight difference of matching connections, found by innovation number.
+    fn genetic_distance(&self, other: &Self) -> f64 {
+        let (a, b) = (&self.genome.connections, &other.genome.connections);
+        let (mut i, mut j) = (0, 0);
+        let (mut matching, mut weight_diff) = (0, 0.);
+        while i < a.len() && j < b.len() {
+            match a[i].innovation.cmp(&b[j].innovation) {
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
+                std::cmp::Ordering::Equal => {
+                    matching += 1;
+                    weight_diff += (a[i].weight - b[j].weight).abs();
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+        let n = a.len().max(b.len());
+        if n == 0 {
+            return 0.;
+        }
+        let unmatched = (a.len() + b.len() - 2 * matching) as f64 / n as f64;
+        let mean_diff = if matching > 0 {
+            weight_diff / matching as f64
+        } else {
+            0.
+        };
+        unmatched + mean_diff
+    }
+
+    /// NEAT crossover: the offspring has this genome's topology, with matching connections and
+    /// neurons (by innovation number and id) combined with `other`'s. `Crossover::Layer` treats the
+    /// whole genome as one layer.
+    fn crossover<R: Rng + ?Sized>(
+        &self,
+        other: &Self,
+        mix: &mut Mixer,
+        rng: &mut R,
+    ) -> Option<Self> {
+        let mut genome = self.genome.clone();
+        let (mut i, b) = (0, &other.genome.connections);
+        genome.connections.iter_mut().for_each(|c| {
+            while i < b.len() && b[i].innovation < c.innovation {
+                i += 1;
+            }
+            if i < b.len() && b[i].innovation == c.innovation {
+                c.weight = mix.gene(c.weight, b[i].weight, rng);
+                c.enabled = mix.pick(c.enabled, b[i].enabled, rng);
+            }
+        });
+        let activations = other
+            .genome
+            .nodes
+            .iter()
+            .map(|n| (n.id, n.activation))
+            .collect::<BTreeMap<_, _>>();
+        genome.nodes.iter_mut().for_each(|n| {
+            if let Some(a) = activations.get(&n.id) {
+                n.activation = mix.pick(n.activation, *a, rng);
+            }
+        });
+        Some(genome.into())
+    }
+
+    /// Input nodes no longer read are removed along with their connections, and those read for
+    /// the first time start unconnected.
+    fn set_inputs(&mut self, inputs: &[usize]) {
+        let read = self.genome.nodes[..self.inputs]
+            .iter()
+            .map(|n| n.id as usize);
+        if read.eq(inputs.iter().cloned()) {
+            return;
+        }
+        let mut genome = self.genome.clone();
+        let kept = |id: u64| id >= INPUT as u64 || inputs.binary_search(&(id as usize)).is_ok();
+        genome.connections.retain(|c| kept(c.from));
+        let input = |id: &usize| NodeGene {
+            id: *id as u64,
+            kind: NodeKind::Input,
+            activation: Activation::Identity,
+        };
+        genome.nodes = inputs
+            .iter()
+            .map(input)
+            .chain(
+                genome
+                    .nodes
+                    .into_iter()
+                    .filter(|n| n.kind != NodeKind::Input),
+            )
+            .collect();
+        *self = genome.into();
+    }
+}
+
+#[cfg(test)]
+mod neat_tests {
+    use super::*;
+    use ndarray_rand::rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn evolve(generations: usize) -> NeatBrain<3, 2> {
+        evolve_with(generations, &StructureMutation::default())
+    }
+
+    fn evolve_with(generations: usize, structure: &StructureMutation) -> NeatBrain<3, 2> {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut brain = NeatBrain::<3, 2>::new_random(&[2], &[], &mut rng);
+        (0..generations).for_each(|_| brain = brain.clone_mutate(0.05, structure, &mut rng));
+        brain
+    }
+
+    #[test]
+    fn starts_layered() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let brain = NeatBrain::<3, 2>::new_random(&[4], &[], &mut rng);
+        let genome = brain.get_genome();
+        assert_eq!(genome.nodes.len(), 3 + 2 + 4);
+        assert_eq!(genome.connections.len(), 3 * 4 + 4 * 2);
+        assert_eq!(brain.order.len(), 4 + 2);
+    }
+
+    #[test]
+    fn topology_grows_acyclic() {
+        let brain = evolve(2000);
+        let genome = brain.get_genome();
+        assert!(genome.nodes.len() > 3 + 2 + 2);
+        assert!(genome
+            .connections
+            .windows(2)
+            .all(|w| w[0].innovation < w[1].innovation));
+        // Every non-input node is evaluated exactly once, so there are no cycles.
+        assert_eq!(brain.order.len(), genome.nodes.len() - 3);
+
+        let mut out = [0.; 2];
+        evolve(2000).feed(&[0.5, -0.5, 1.], &mut out);
+        assert!(out.iter().all(|o| o.is_finite()));
+    }
+
+    #[test]
+    fn topology_fixed_without_structure_mutation() {
+        let structure = StructureMutation {
+            add_connection_prob: 0.,
+            add_node_prob: 0.,
+            toggle_connection_prob: 0.,
+            change_activation_prob: 0.,
+            ..StructureMutation::default()
+        };
+        let genome = evolve_with(2000, &structure).get_genome().clone();
+        assert_eq!(genome.nodes.len(), 3 + 2 + 2);
+        assert_eq!(genome.connections.len(), 3 * 2 + 2 * 2);
+        assert!(genome.connections.iter().all(|c| c.enabled));
+    }
+
+    #[test]
+    fn innovations_shared_across_lineages() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let a = NeatBrain::<3, 2>::new_random(&[2], &[], &mut rng);
+        let b = NeatBrain::<3, 2>::new_random(&[2], &[], &mut rng);
+        let innovations = |b: &NeatBrain<3, 2>| {
+            b.get_genome()
+                .connections
+                .iter()
+                .map(|c| c.innovation)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(innovations(&a), innovations(&b));
+        assert_eq!(a.genetic_distance(&a), 0.);
+        assert!(a.genetic_distance(&b) > 0.);
+        assert!(evolve(0).genetic_distance(&evolve(500)) > 0.);
+    }
+
+    #[test]
+    fn direct_connections() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let mut neat = NeatBrain::<2, 1>::new_random(&[], &[], &mut rng);
+        let w = neat
+            .get_genome()
+            .connections
+            .iter()
+            .map(|c| (c.from, c.weight))
+            .collect::<BTreeMap<_, _>>();
+        let mut out = [0.];
+        neat.feed(&[1., 2.], &mut out);
+        assert!((out[0] - (w[&0] + 2. * w[&1])).abs() < 1e-12);
+    }
+
+    #[test]
+    fn crossover_keeps_first_topology() {
+        let (a, b) = (evolve(1000), evolve(0));
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let mut mix = Mixer::new(crate::model::Crossover::Uniform, &mut rng);
+        let mut c = a.crossover(&b, &mut mix, &mut rng).unwrap();
+        let innovations = |b: &NeatBrain<3, 2>| {
+            b.get_genome()
+                .connections
+                .iter()
+                .map(|c| c.innovation)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(innovations(&c), innovations(&a));
+        assert!(c.genetic_distance(&a) > 0.);
+        assert_eq!(c.order.len(), c.get_genome().nodes.len() - 3);
+
+        let mut out = [0.; 2];
+        c.feed(&[0.5, -0.5, 1.], &mut out);
+        assert!(out.iter().all(|o| o.is_finite()));
+    }
+
+    #[test]
+    fn serde_rebuilds_network() {
+        let mut brain = evolve(500);
+        let mut loaded: NeatBrain<3, 2> =
+            bincode::deserialize(&bincode::serialize(&brain).unwrap()).unwrap();
+        let (mut a, mut b) = ([0.; 2], [0.; 2]);
+        brain.feed(&[1., 0., -1.], &mut a);
+        loaded.feed(&[1., 0., -1.], &mut b);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn reads_only_set_inputs() {
+        let mut brain = evolve(500);
+        let (mut a, mut b) = ([0.; 2], [0.; 2]);
+        brain.feed(&[1., 0., -1.], &mut a);
+        brain.set_inputs(&[0, 2]);
+        let genome = brain.get_genome();
+        assert!(genome.connections.iter().all(|c| c.from != 1));
+        assert_eq!(brain.order.len(), genome.nodes.len() - 2);
+        brain.feed(&[1., 5., -1.], &mut b);
+        assert_eq!(a, b);
+
+        // Input 1 comes back unconnected.
+        brain.set_inputs(&[0, 1, 2]);
+        assert_eq!(brain.get_genome().nodes[1].id, 1);
+        brain.feed(&[1., 5., -1.], &mut b);
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,274 @@
+use crate::model::brain::{
+    align_inputs, rms_distance, select_inputs, spread_inputs, Brain, Mixer, StructureMutation,
+};
+use ndarray::{arr1, concatenate, Array, Array1, Array2, Axis, Dim};
+use ndarray_rand::rand::Rng;
+use ndarray_rand::rand_distr::Normal;
+use ndarray_rand::RandomExt;
+use serde::{Deserialize, Serialize};
+
+/// One layer of gated recurrent units.
+///
+/// Each gate is a single matrix applied to the layer input, the (reset) hidden state and a
+/// constant 1 for the bias, so it has shape `[hidden, in + hidden + 1]`.
+#[derive(Clone, Serialize, Deserialize)]
+struct GruLayer {
+    update: Array<f64, Dim<[usize; 2]>>,
+    reset: Array<f64, Dim<[usize; 2]>>,
+    candidate: Array<f64, Dim<[usize; 2]>>,
+    hidden: Array1<f64>,
+}
+
+impl GruLayer {
+    fn new_random<R: Rng + ?Sized>(input: usize, hidden: usize, rng: &mut R) -> Self {
+        let d = Normal::new(0., 1.).unwrap();
+        let shape = [hidden, input + hidden + 1];
+        Self {
+            update: Array::random_using(shape, d, rng),
+            reset: Array::random_using(shape, d, rng),
+            candidate: Array::random_using(shape, d, rng),
+            hidden: Array1::zeros(hidden),
+        }
+    }
+
+    fn feed(&mut self, input: &Array1<f64>) -> &Array1<f64> {
+        let one = arr1(&[1.]);
+        let x = concatenate![Axis(0), *input, self.hidden, one];
+        let update = self.update.dot(&x).mapv(sigmoid);
+        let reset = self.reset.dot(&x).mapv(sigmoid);
+
+        let x = concatenate![Axis(0), *input, &reset * &self.hidden, one];
+        let candidate = self.candidate.dot(&x).mapv(f64::tanh);
+
+        self.hidden = &update * &self.hidden + (1. - &update) * &candidate;
+        &self.hidden
+    }
+}
+
+/// A stack of gated recurrent unit layers followed by a linear output layer.
+///
+/// Unlike [`NeuralBrain`](crate::model::NeuralBrain) its outputs depend on everything it has been
+/// fed so far, through the hidden state of each layer.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GruBrain<const INPUT: usize, const OUTPUT: usize> {
+    // Indices of the inputs read, one per input column of the first layer.
+    inputs: Vec<usize>,
+    layers: Vec<GruLayer>,
+    output: Array<f64, Dim<[usize; 2]>>,
+}
+
+impl<const INPUT: usize, const OUTPUT: usize> GruBrain<INPUT, OUTPUT> {
+    /// Random normal weights with recurrent layers of the given sizes and zero hidden state,
+    /// reading every input.
+    pub fn new_random<R: Rng + ?Sized>(shape: &[usize], rng: &mut R) -> Self {
+        let mut last_size = INPUT;
+        let layers = shape
+            .iter()
+            .map(|next_size| {
+                let layer = GruLayer::new_random(last_size, *next_size, rng);
+                last_size = *next_size;
+                layer
+            })
+            .collect();
+        let output = Array::random_using([OUTPUT, last_size], Normal::new(0., 1.).unwrap(), rng);
+        Self {
+            inputs: (0..INPUT).collect(),
+            layers,
+            output,
+        }
+    }
+
+    /// Current hidden state of each layer.
+    pub fn get_hidden(&self) -> impl Iterator<Item = &Array1<f64>> {
+        self.layers.iter().map(|l| &l.hidden)
+    }
+
+    fn weights(&self) -> Vec<&Array<f64, Dim<[usize; 2]>>> {
+        self.layers
+            .iter()
+            .flat_map(|l| [&l.update, &l.reset, &l.candidate])
+            .chain(std::iter::once(&self.output))
+            .collect()
+    }
+
+    // The matrices reading the inputs: the gates of the first layer, or the output without any.
+    fn first_mats(&self) -> Vec<&Array2<f64>> {
+        match self.layers.first() {
+            Some(l) => vec![&l.update, &l.reset, &l.candidate],
+            None => vec![&self.output],
+        }
+    }
+
+    // Replace each of `first_mats` by `f` of its index and itself.
+    fn map_first_mats<F: FnMut(usize, &Array2<f64>) -> Array2<f64>>(&mut self, mut f: F) {
+        match self.layers.first_mut() {
+            Some(l) => [&mut l.update, &mut l.reset, &mut l.candidate]
+                .iter_mut()
+                .enumerate()
+                .for_each(|(k, m)| **m = f(k, m)),
+            None => self.output = f(0, &self.output),
+        }
+    }
+}
+
+impl<const INPUT: usize, const OUTPUT: usize> Brain<INPUT, OUTPUT> for GruBrain<INPUT, OUTPUT> {
+    /// Mutated weights, the copy starts with zero hidden state.
+    fn clone_mutate<R: Rng + ?Sized>(&self, std: f64, _: &StructureMutation, rng: &mut R) -> Self {
+        let d = Normal::new(0., std).unwrap();
+        let mut mutate = |m: &Array<f64, Dim<[usize; 2]>>| {
+            let shape = [m.nrows(), m.ncols()];
+            m + &Array::random_using(shape, d, rng)
+        };
+        let layers = self
+            .layers
+            .iter()
+            .map(|l| GruLayer {
+                update: mutate(&l.update),
+                reset: mutate(&l.reset),
+                candidate: mutate(&l.candidate),
+                hidden: Array1::zeros(l.hidden.len()),
+            })
+            .collect();
+        let output = mutate(&self.output);
+        Self {
+            inputs: self.inputs.clone(),
+            layers,
+            output,
+        }
+    }
+
+    fn feed(&mut self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]) {
+        let read = self
+            .inputs
+            .iter()
+            .map(|i| inputs[*i])
+            .collect::<Array1<_>>();
+        let buff = self
+            .layers
+            .iter_mut()
+            .fold(read, |buff, layer| layer.feed(&buff).clone());
+        let buff = self.output.dot(&buff);
+
+        outputs.iter_mut().enumerate().for_each(|(i, o)| {
+            *o = buff[i];
+        });
+    }
+
+    fn size(&self) -> usize {
+        self.weights().iter().map(|m| m.len()).sum()
+    }
+
+    /// Root mean square difference of the weights, ignoring hidden state.
+    ///
+    /// Weights present in only one of the brains, where layer shapes or the inputs read differ,
+    /// are compared to zero.
+    fn genetic_distance(&self, other: &Self) -> f64 {
+        let spread = |b: &Self| {
+            let mut b = b.clone();
+            let inputs = std::mem::take(&mut b.inputs);
+            b.map_first_mats(|_, m| spread_inputs(m, &inputs, INPUT));
+            b
+        };
+        rms_distance(&spread(self).weights(), &spread(other).weights())
+    }
+
+    /// Layer by layer, between brains with the same layer sizes only. The offspring reads the
+    /// inputs this brain reads and starts with zero hidden state.
+    fn crossover<R: Rng + ?Sized>(
+        &self,
+        other: &Self,
+        mix: &mut Mixer,
+        rng: &mut R,
+    ) -> Option<Self> {
+        let sizes = |b: &Self| b.layers.iter().map(|l| l.hidden.len()).collect::<Vec<_>>();
+        if sizes(self) != sizes(other) {
+            return None;
+        }
+        let mine = self.first_mats();
+        let mut other = other.clone();
+        let theirs = std::mem::replace(&mut other.inputs, self.inputs.clone());
+        other.map_first_mats(|k, m| align_inputs(mine[k], &self.inputs, m, &theirs));
+        let layers = self
+            .layers
+            .iter()
+            .zip(other.layers.iter())
+            .map(|(a, b)| {
+                mix.next_layer(rng);
+                GruLayer {
+                    update: mix.genes(&a.update, &b.update, rng),
+                    reset: mix.genes(&a.reset, &b.reset, rng),
+                    candidate: mix.genes(&a.candidate, &b.candidate, rng),
+                    hidden: Array1::zeros(a.hidden.len()),
+                }
+            })
+            .collect();
+        mix.next_layer(rng);
+        let output = mix.genes(&self.output, &other.output, rng);
+        Some(Self {
+            inputs: self.inputs.clone(),
+            layers,
+            output,
+        })
+    }
+
+    fn set_inputs(&mut self, inputs: &[usize]) {
+        if self.inputs != inputs {
+            let old = std::mem::replace(&mut self.inputs, inputs.to_vec());
+            self.map_first_mats(|_, m| select_inputs(m, &old, inputs));
+        }
+    }
+}
+
+fn sigmoid(f: f64) -> f64 {
+    1. / (1. + (-f).exp())
+}
+
+#[cfg(test)]
+mod recurrent_tests {
+    use super::*;
+    use ndarray_rand::rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn remembers_past_inputs() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut a = GruBrain::<2, 2>::new_random(&[4], &mut rng);
+        let mut b = a.clone();
+        let (mut out_a, mut out_b) = ([0.; 2], [0.; 2]);
+
+        a.feed(&[1., -1.], &mut out_a);
+        b.feed(&[-1., 1.], &mut out_b);
+        a.feed(&[0., 0.], &mut out_a);
+        b.feed(&[0., 0.], &mut out_b);
+        assert_ne!(out_a, out_b);
+    }
+
+    #[test]
+    fn mutation() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut a = GruBrain::<2, 2>::new_random(&[4, 3], &mut rng);
+        a.feed(&[1., 1.], &mut [0.; 2]);
+        let b = a.clone_mutate(0.1, &StructureMutation::default(), &mut rng);
+        assert!(b.get_hidden().all(|h| h.iter().all(|v| *v == 0.)));
+        assert_eq!(a.genetic_distance(&a), 0.);
+        assert!(a.genetic_distance(&b) > 0.);
+    }
+
+    #[test]
+    fn reads_only_set_inputs() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let a = GruBrain::<3, 2>::new_random(&[4], &mut rng);
+        let mut b = a.clone();
+        b.set_inputs(&[0, 2]);
+        assert!(b.size() < a.size());
+        let (mut out_a, mut out_b) = ([0.; 2], [0.; 2]);
+        a.clone().feed(&[1., 0., -1.], &mut out_a);
+        b.feed(&[1., 5., -1.], &mut out_b);
+        assert_eq!(out_a, out_b);
+
+        let mut mix = Mixer::new(crate::model::Crossover::Uniform, &mut rng);
+        let c = b.crossover(&a, &mut mix, &mut rng).unwrap();
+        assert_eq!(c.size(), b.size());
+        assert!(a.genetic_distance(&b) > 0.);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::model::brain::{Brain, Mixer, StructureMutation};
+use crate::model::config::WorldConfig;
+use crate::model::creature::{Action, MovementAction, Observation, TurningAction};
+use ndarray_rand::rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// Grass (0-1) below which a grazer considers its tile depleted.
+pub const GRAZER_DEPLETED: f64 = 0.1;
+
+/// A fixed behaviour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Script {
+    /// Eats until its tile is depleted, then walks straight on to find another.
+    Grazer,
+    /// Turns towards the nearest creature in sight, walks up to it and bites. Circles when
+    /// nothing is in sight.
+    Predator,
+}
+
+impl Script {
+    /// Vegetable efficiency of creatures following the script, which never mutates: grazers live
+    /// on grass alone and predators on meat alone.
+    pub fn veg_eff(self) -> f64 {
+        match self {
+            Script::Grazer => 1.,
+            Script::Predator => 0.,
+        }
+    }
+}
+
+/// A hand-written controller following a [`Script`], as a baseline for evolved brains.
+///
+/// It reads the brain inputs of a [`Creature`](crate::model::Creature), an [`Observation`] followed
+/// by memory, and scores the chosen actions 1 and every other 0. Creatures it drives always take
+/// those actions, whatever the world's [`ActionSelection`](crate::model::ActionSelection), so it
+/// behaves deterministically. Both scripts replicate once they have enough energy. Scripts do not mutate, so offspring behave like their parents.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScriptedBrain {
+    script: Script,
+    // Observed energy above which to replicate.
+    replicate_energy: f64,
+    bite_dist: f64,
+}
+
+impl ScriptedBrain {
+    /// Follow `script` in a world with the constants of `config`.
+    pub fn new(script: Script, config: &WorldConfig) -> Self {
+        Self {
+            script,
+            replicate_energy: config.replicate_min_energy as f64 / config.starting_energy as f64,
+            bite_dist: config.bite_dist,
+        }
+    }
+
+    pub fn get_script(&self) -> Script {
+        self.script
+    }
+
+    fn decide(&self, o: &Observation) -> (TurningAction, MovementAction, Action) {
+        let replicate = o.energy > self.replicate_energy;
+        match self.script {
+            Script::Grazer => {
+                let mid = Observation::GRASS_NEIGHBORS / 2;
+                let grass = o.neighboring_grass[Observation::neighbor_index(mid, mid)];
+                if replicate {
+                    (TurningAction::WAIT, MovementAction::WAIT, Action::REPLICATE)
+                } else if grass > GRAZER_DEPLETED {
+                    (TurningAction::WAIT, MovementAction::WAIT, Action::EAT)
+                } else {
+                    (TurningAction::WAIT, MovementAction::FORWARD, Action::WAIT)
+                }
+            }
+            Script::Predator => {
+                // Slots run clockwise to counter-clockwise, the middle one is straight ahead unless
+                // the eyes have an even number of sites.
+                let nearest = o
+                    .dists
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, d)| **d < Observation::MAX_DIST)
+                    .fold(None, |best: Option<(usize, f64)>, (i, d)| match best {
+                        Some((_, bd)) if bd <= *d => best,
+                        _ => Some((i, *d)),
+                    });
+                let mid = Observation::MAX_SITES / 2;
+                match nearest {
+                    Some((site, d)) => {
+                        let turn = match site.cmp(&mid) {
+                            std::cmp::Ordering::Less => TurningAction::RIGHT,
+                            std::cmp::Ordering::Equal => TurningAction::WAIT,
+                            std::cmp::Ordering::Greater => TurningAction::LEFT,
+                        };
+                        if d <= self.bite_dist {
+                            (turn, MovementAction::WAIT, Action::BITE)
+                        } else if replicate {
+                            (turn, MovementAction::FORWARD, Action::REPLICATE)
+                        } else {
+                            (turn, MovementAction::FORWARD, Action::WAIT)
+                        }
+                    }
+                    None if replicate => {
+                        (TurningAction::LEFT, MovementAction::WAIT, Action::REPLICATE)
+                    }
+                    None => (TurningAction::LEFT, MovementAction::FORWARD, Action::WAIT),
+                }
+            }
+        }
+    }
+}
+
+impl<const INPUT: usize, const OUTPUT: usize> Brain<INPUT, OUTPUT> for ScriptedBrain {
+    /// An identical copy.
+    fn clone_mutate<R: Rng + ?Sized>(&self, _: f64, _: &StructureMutation, _: &mut R) -> Self {
+        self.clone()
+    }
+
+    fn feed(&mut self, inputs: &[f64; INPUT], outputs: &mut [f64; OUTPUT]) {
+        let (turn, movement, action) = self.decide(&Observation::from_inputs(inputs));
+        let j = TurningAction::NUM_ACTIONS;
+        let k = j + MovementAction::NUM_ACTIONS;
+        outputs.iter_mut().for_each(|o| *o = 0.);
+        outputs[turn as usize] = 1.;
+        outputs[j + movement as usize] = 1.;
+        outputs[k + action as usize] = 1.;
+    }
+
+    /// Zero between brains following the same script, infinite otherwise.
+    fn genetic_distance(&self, other: &Self) -> f64 {
+        if self.script == other.script {
+            0.
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    fn size(&self) -> usize {
+        0
+    }
+
+    /// Only brains following the same script can be combined.
+    fn crossover<R: Rng + ?Sized>(&self, other: &Self, _: &mut Mixer, _: &mut R) -> Option<Self> {
+        if self.script == other.script {
+            Some(self.clone())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod scripted_tests {
+    use super::*;
+    use crate::model::creature::Creature;
+
+    const INPUTS: usize = Creature::NUM_BRAIN_INPUTS;
+    const OUTPUTS: usize = Creature::NUM_BRAIN_OUTPUTS;
+
+    fn decide(script: Script, o: &Observation) -> (TurningAction, MovementAction, Action) {
+        let mut brain = ScriptedBrain::new(script, &WorldConfig::default());
+        let mut inputs = [0.; INPUTS];
+        inputs[..Observation::NUM_INPUTS].copy_from_slice(&o.inputs());
+        let mut outputs = [0.; OUTPUTS];
+        Brain::<INPUTS, OUTPUTS>::feed(&mut brain, &inputs, &mut outputs);
+        assert_eq!(outputs.iter().filter(|o| **o == 1.).count(), 3);
+        brain.decide(o)
+    }
+
+    fn empty() -> Observation {
+        let mut o = Observation::new_empty();
+        o.dists.iter_mut().for_each(|d| *d = Observation::MAX_DIST);
+        o.energy = 1.;
+        o
+    }
+
+    #[test]
+    fn grazer_eats_then_moves_on() {
+        let mut o = empty();
+        o.neighboring_grass.iter_mut().for_each(|g| *g = 1.);
+        assert_eq!(decide(Script::Grazer, &o).2, Action::EAT);
+
+        o.neighboring_grass[Observation::neighbor_index(1, 1)] = 0.;
+        let (_, movement, action) = decide(Script::Grazer, &o);
+        assert_eq!(movement, MovementAction::FORWARD);
+        assert_eq!(action, Action::WAIT);
+
+        o.energy = 10.;
+        assert_eq!(decide(Script::Grazer, &o).2, Action::REPLICATE);
+    }
+
+    #[test]
+    fn predator_chases_and_bites() {
+        let mut o = empty();
+        assert_eq!(decide(Script::Predator, &o).0, TurningAction::LEFT);
+
+        o.dists[0] = 5.;
+        o.dists[6] = 8.;
+        let (turn, movement, action) = decide(Script::Predator, &o);
+        assert_eq!(turn, TurningAction::RIGHT);
+        assert_eq!(movement, MovementAction::FORWARD);
+        assert_eq!(action, Action::WAIT);
+
+        o.dists[6] = 0.5;
+        let (turn, movement, action) = decide(Script::Predator, &o);
+        assert_eq!(turn, TurningAction::LEFT);
+        assert_eq!(movement, MovementAction::WAIT);
+        assert_eq!(action, Action::BITE);
+    }
+}
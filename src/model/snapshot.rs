@@ -0,0 +1,200 @@
+use crate::model::world::World;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+// Snapshots are the magic bytes, a little endian format version, then the bincode encoded world.
+const SNAPSHOT_MAGIC: &[u8; 8] = b"RCWORLD\0";
+
+/// Version of the snapshot format written by [`World::save_snapshot`].
+///
+/// Bump whenever the serialized layout of the world, its creatures or their brains changes.
+pub const SNAPSHOT_VERSION: u32 = 18;
+
+/// Why a snapshot could not be saved or loaded.
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Encoding(bincode::Error),
+    NotASnapshot,
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot io error: {}", e),
+            SnapshotError::Encoding(e) => write!(f, "malformed snapshot: {}", e),
+            SnapshotError::NotASnapshot => write!(f, "not a world snapshot"),
+            SnapshotError::UnsupportedVersion(v) => write!(
+                f,
+                "snapshot version {} is not supported (expected {})",
+                v, SNAPSHOT_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(e: bincode::Error) -> Self {
+        SnapshotError::Encoding(e)
+    }
+}
+
+impl World {
+    /// Write the complete simulation state, including the random streams, to `w`.
+    ///
+    /// A world loaded back with [`World::load_snapshot`] continues exactly as this one would.
+    /// Worlds with [custom brains](crate::model::AnyBrain::Custom) cannot be saved.
+    pub fn save_snapshot<W: Write>(&self, mut w: W) -> Result<(), SnapshotError> {
+        w.write_all(SNAPSHOT_MAGIC)?;
+        w.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut w, self)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Read a world written by [`World::save_snapshot`].
+    pub fn load_snapshot<R: Read>(mut r: R) -> Result<World, SnapshotError> {
+        let mut magic = [0u8; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+        let mut version = [0u8; 4];
+        r.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        Ok(bincode::deserialize_from(r)?)
+    }
+
+    /// Save a snapshot to the file at `path`, replacing it if it exists.
+    ///
+    /// The snapshot is written to `<path>.tmp` and moved over `path` once it is on disk, so a run
+    /// killed while saving keeps its previous snapshot.
+    pub fn save_snapshot_file<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut w = BufWriter::new(File::create(&tmp)?);
+        self.save_snapshot(&mut w)?;
+        w.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Load a snapshot from the file at `path`.
+    pub fn load_snapshot_file<P: AsRef<Path>>(path: P) -> Result<World, SnapshotError> {
+        World::load_snapshot(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use super::*;
+    use crate::model::{BrainKind, WorldConfig};
+
+    fn to_bytes(w: &World) -> Vec<u8> {
+        let mut buff = vec![];
+        w.save_snapshot(&mut buff).unwrap();
+        buff
+    }
+
+    #[test]
+    fn roundtrip_resumes_exactly() {
+        let mut w = World::new_seeded(30, 30, 20, 3);
+        (0..200).for_each(|_| w.update());
+
+        let mut loaded = World::load_snapshot(to_bytes(&w).as_slice()).unwrap();
+        assert_eq!(to_bytes(&w), to_bytes(&loaded));
+
+        (0..200).for_each(|_| {
+            w.update();
+            loaded.update();
+        });
+        assert_eq!(to_bytes(&w), to_bytes(&loaded));
+    }
+
+    fn roundtrip_with_brain(brain: BrainKind) {
+        let config = WorldConfig {
+            brain,
+            replicate_min_energy: 5000,
+            replicate_cost: 1000,
+            ..WorldConfig::default()
+        };
+        let mut w = World::new_with_config(30, 30, 20, 3, config);
+        (0..200).for_each(|_| w.update());
+        assert!(w
+            .get_creature_slice()
+            .iter()
+            .all(|c| c.get_brain().kind() == Some(brain)));
+
+        let mut loaded = World::load_snapshot(to_bytes(&w).as_slice()).unwrap();
+        (0..200).for_each(|_| {
+            w.update();
+            loaded.update();
+        });
+        assert_eq!(to_bytes(&w), to_bytes(&loaded));
+    }
+
+    #[test]
+    fn roundtrip_keeps_recurrent_state() {
+        roundtrip_with_brain(BrainKind::Gru);
+    }
+
+    #[test]
+    fn roundtrip_rebuilds_neat_networks() {
+        roundtrip_with_brain(BrainKind::Neat);
+    }
+
+    #[test]
+    fn roundtrip_keeps_scripts() {
+        roundtrip_with_brain(BrainKind::Grazer);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let w = World::new_seeded(10, 10, 5, 3);
+        let mut bytes = to_bytes(&w);
+        bytes[SNAPSHOT_MAGIC.len()] += 1;
+        match World::load_snapshot(bytes.as_slice()) {
+            Err(SnapshotError::UnsupportedVersion(v)) => assert_eq!(v, SNAPSHOT_VERSION + 1),
+            _ => panic!("Expected a version error"),
+        }
+    }
+
+    #[test]
+    fn file_is_replaced_whole() {
+        let dir = std::env::temp_dir().join(format!("rcw_snapshot_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("snapshot.rcw");
+        std::fs::write(&path, b"an older snapshot").unwrap();
+
+        let w = World::new_seeded(10, 10, 5, 3);
+        w.save_snapshot_file(&path).unwrap();
+        assert!(!dir.join("snapshot.rcw.tmp").exists());
+        let loaded = World::load_snapshot_file(&path).unwrap();
+        assert_eq!(to_bytes(&w), to_bytes(&loaded));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        let bytes = b"definitely not a world snapshot";
+        assert!(matches!(
+            World::load_snapshot(&bytes[..]),
+            Err(SnapshotError::NotASnapshot)
+        ));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::model::brain::Brain;
+use crate::model::creature::{Creature, CreatureBrain};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The heritable traits of a creature, compared to tell species apart.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Genome {
+    brain: CreatureBrain,
+    veg_eff: f64,
+}
+
+impl Genome {
+    pub fn of(c: &Creature) -> Self {
+        Self {
+            brain: c.get_brain().clone(),
+            veg_eff: c.get_veg_eff(),
+        }
+    }
+
+    /// Genetic distance of the brains plus the difference in diet.
+    pub fn distance_to(&self, c: &Creature) -> f64 {
+        self.brain.genetic_distance(c.get_brain()) + (self.veg_eff - c.get_veg_eff()).abs()
+    }
+}
+
+/// Founding genome of every living family.
+///
+/// Offspring which drift further than a threshold from their family's founder start a family of
+/// their own, see [`WorldConfig::speciation_threshold`](crate::model::WorldConfig).
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Species {
+    founders: BTreeMap<usize, Genome>,
+}
+
+impl Species {
+    /// Make `c` the founder of its family.
+    pub fn found(&mut self, c: &Creature) {
+        self.founders.insert(c.get_fam(), Genome::of(c));
+    }
+
+    pub fn get_founder(&self, fam: usize) -> Option<&Genome> {
+        self.founders.get(&fam)
+    }
+
+    /// Distance of `c` from its family's founder, `None` if the family has no recorded founder.
+    pub fn distance_from_founder(&self, c: &Creature) -> Option<f64> {
+        self.get_founder(c.get_fam()).map(|f| f.distance_to(c))
+    }
+
+    /// Forget the founders of families for which `living` returns false.
+    pub fn retain<F: Fn(usize) -> bool>(&mut self, living: F) {
+        self.founders.retain(|fam, _| living(*fam));
+    }
+
+    pub fn len(&self) -> usize {
+        self.founders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.founders.is_empty()
+    }
+}
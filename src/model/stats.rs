@@ -0,0 +1,337 @@
+use crate::model::{Action, AnyBrain, BrainKind, MovementAction, TurningAction};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Why a creature was removed from the world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeathCause {
+    /// Ran out of energy on its own.
+    Starvation,
+    /// Reached the configured maximum age.
+    OldAge,
+    /// Had its last energy bitten away.
+    Predation,
+}
+
+/// Event counts accumulated by [`World::update`](crate::model::World::update) between calls to
+/// [`World::collect_stats`](crate::model::World::collect_stats).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TickCounts {
+    /// Creatures born through replication or mating.
+    pub births: u64,
+    /// Of the births, those of two parents mating.
+    pub matings: u64,
+    /// Creatures spawned to keep up the minimum population or family count.
+    pub spawns: u64,
+    /// Newborns which founded a family of their own.
+    pub speciations: u64,
+    pub deaths_starvation: u64,
+    pub deaths_old_age: u64,
+    pub deaths_predation: u64,
+    /// How often each action was chosen, indexed by the action's discriminant.
+    pub turn_actions: [u64; TurningAction::NUM_ACTIONS],
+    pub move_actions: [u64; MovementAction::NUM_ACTIONS],
+    pub actions: [u64; Action::NUM_ACTIONS],
+}
+
+impl TickCounts {
+    pub(crate) fn count_death(&mut self, cause: DeathCause) {
+        match cause {
+            DeathCause::Starvation => self.deaths_starvation += 1,
+            DeathCause::OldAge => self.deaths_old_age += 1,
+            DeathCause::Predation => self.deaths_predation += 1,
+        }
+    }
+
+    pub(crate) fn count_actions(&mut self, turn: TurningAction, mov: MovementAction, act: Action) {
+        self.turn_actions[turn as usize] += 1;
+        self.move_actions[mov as usize] += 1;
+        self.actions[act as usize] += 1;
+    }
+}
+
+/// Summary of a distribution of values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Quantiles {
+    pub min: f64,
+    pub q25: f64,
+    pub median: f64,
+    pub q75: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl Quantiles {
+    /// Summarize the finite `values`, all zeros if there are none.
+    pub fn from_values(mut values: Vec<f64>) -> Self {
+        values.retain(|v| v.is_finite());
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_by(f64::total_cmp);
+        let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+        Self {
+            min: values[0],
+            q25: at(0.25),
+            median: at(0.5),
+            q75: at(0.75),
+            max: values[values.len() - 1],
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+        }
+    }
+}
+
+/// Number of living creatures with each type of brain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrainPopulation {
+    pub feed_forward: usize,
+    pub gru: usize,
+    pub neat: usize,
+    pub grazer: usize,
+    pub predator: usize,
+    pub custom: usize,
+}
+
+impl BrainPopulation {
+    pub fn count<'a, I, const INPUT: usize, const OUTPUT: usize>(brains: I) -> Self
+    where
+        I: IntoIterator<Item = &'a AnyBrain<INPUT, OUTPUT>>,
+    {
+        let mut p = Self::default();
+        brains.into_iter().for_each(|b| match b.kind() {
+            Some(BrainKind::FeedForward) => p.feed_forward += 1,
+            Some(BrainKind::Gru) => p.gru += 1,
+            Some(BrainKind::Neat) => p.neat += 1,
+            Some(BrainKind::Grazer) => p.grazer += 1,
+            Some(BrainKind::Predator) => p.predator += 1,
+            None => p.custom += 1,
+        });
+        p
+    }
+}
+
+/// State of the world at one tick, plus what happened since the previous [`Stats`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub tick: u64,
+    pub population: usize,
+    pub families: usize,
+    pub total_grass: u64,
+    pub total_energy: u64,
+    pub veg_eff: Quantiles,
+    pub age: Quantiles,
+    /// Of stochastic action selection, see [`Creature::get_temperature`](crate::model::Creature::get_temperature).
+    pub temperature: Quantiles,
+    /// See [`Creature::get_brain_mut_rate`](crate::model::Creature::get_brain_mut_rate).
+    pub brain_mut_rate: Quantiles,
+    /// See [`Creature::get_trait_mut_rate`](crate::model::Creature::get_trait_mut_rate).
+    pub trait_mut_rate: Quantiles,
+    /// Of the eyes, see [`Creature::get_vision`](crate::model::Creature::get_vision).
+    pub vision_sites: Quantiles,
+    pub vision_range: Quantiles,
+    pub vision_dist: Quantiles,
+    /// Number of brain weights, see [`Brain::size`](crate::model::Brain::size).
+    pub brain_size: Quantiles,
+    pub brains: BrainPopulation,
+    pub counts: TickCounts,
+}
+
+impl Stats {
+    /// Column names matching [`Stats::csv_row`].
+    pub fn csv_header() -> String {
+        let mut cols = vec![
+            "tick",
+            "population",
+            "families",
+            "births",
+            "matings",
+            "spawns",
+            "speciations",
+            "deaths_starvation",
+            "deaths_old_age",
+            "deaths_predation",
+            "total_grass",
+            "total_energy",
+            "pop_feed_forward",
+            "pop_gru",
+            "pop_neat",
+            "pop_grazer",
+            "pop_predator",
+            "pop_custom",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+        [
+            "veg_eff",
+            "age",
+            "temperature",
+            "brain_mut_rate",
+            "trait_mut_rate",
+            "vision_sites",
+            "vision_range",
+            "vision_dist",
+            "brain_size",
+        ]
+        .iter()
+        .for_each(|q| {
+            ["min", "q25", "median", "q75", "max", "mean"]
+                .iter()
+                .for_each(|s| cols.push(format!("{}_{}", q, s)))
+        });
+        ["turn_wait", "turn_left", "turn_right"]
+            .iter()
+            .chain(["move_wait", "move_forward"].iter())
+            .chain(
+                [
+                    "act_wait",
+                    "act_eat",
+                    "act_bite",
+                    "act_replicate",
+                    "act_mate",
+                ]
+                .iter(),
+            )
+            .for_each(|s| cols.push(s.to_string()));
+        cols.join(",")
+    }
+
+    /// Values as a line of CSV, without the trailing newline.
+    pub fn csv_row(&self) -> String {
+        let c = &self.counts;
+        let mut cols = vec![
+            self.tick.to_string(),
+            self.population.to_string(),
+            self.families.to_string(),
+            c.births.to_string(),
+            c.matings.to_string(),
+            c.spawns.to_string(),
+            c.speciations.to_string(),
+            c.deaths_starvation.to_string(),
+            c.deaths_old_age.to_string(),
+            c.deaths_predation.to_string(),
+            self.total_grass.to_string(),
+            self.total_energy.to_string(),
+            self.brains.feed_forward.to_string(),
+            self.brains.gru.to_string(),
+            self.brains.neat.to_string(),
+            self.brains.grazer.to_string(),
+            self.brains.predator.to_string(),
+            self.brains.custom.to_string(),
+        ];
+        [
+            self.veg_eff,
+            self.age,
+            self.temperature,
+            self.brain_mut_rate,
+            self.trait_mut_rate,
+            self.vision_sites,
+            self.vision_range,
+            self.vision_dist,
+            self.brain_size,
+        ]
+        .iter()
+        .for_each(|q| {
+            [q.min, q.q25, q.median, q.q75, q.max, q.mean]
+                .iter()
+                .for_each(|v| cols.push(v.to_string()))
+        });
+        c.turn_actions
+            .iter()
+            .chain(c.move_actions.iter())
+            .chain(c.actions.iter())
+            .for_each(|v| cols.push(v.to_string()));
+        cols.join(",")
+    }
+}
+
+/// Writes [`Stats`] as CSV, one row per call to [`StatsWriter::write`].
+pub struct StatsWriter<W: Write> {
+    out: W,
+}
+
+impl StatsWriter<BufWriter<File>> {
+    /// Create (or truncate) the CSV file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Open the CSV file at `path` to add rows after those already in it, writing the header
+    /// only if the file is new or empty.
+    pub fn append<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let empty = file.metadata()?.len() == 0;
+        let out = BufWriter::new(file);
+        if empty {
+            Self::new(out)
+        } else {
+            Ok(Self { out })
+        }
+    }
+}
+
+impl<W: Write> StatsWriter<W> {
+    /// Wrap `out`, writing the header line immediately.
+    pub fn new(mut out: W) -> std::io::Result<Self> {
+        writeln!(out, "{}", Stats::csv_header())?;
+        Ok(Self { out })
+    }
+
+    pub fn write(&mut self, stats: &Stats) -> std::io::Result<()> {
+        writeln!(self.out, "{}", stats.csv_row())
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::*;
+
+    #[test]
+    fn quantiles() {
+        let q = Quantiles::from_values(vec![4., 0., 2., 1., 3.]);
+        assert_eq!(q.min, 0.);
+        assert_eq!(q.q25, 1.);
+        assert_eq!(q.median, 2.);
+        assert_eq!(q.q75, 3.);
+        assert_eq!(q.max, 4.);
+        assert_eq!(q.mean, 2.);
+    }
+
+    #[test]
+    fn quantiles_skip_non_finite() {
+        let q = Quantiles::from_values(vec![4., f64::NAN, 0., 2., f64::INFINITY, 1., 3.]);
+        assert_eq!(q, Quantiles::from_values(vec![4., 0., 2., 1., 3.]));
+        assert_eq!(Quantiles::from_values(vec![f64::NAN]), Quantiles::default());
+    }
+
+    #[test]
+    fn header_matches_row() {
+        let header = Stats::csv_header();
+        let row = Stats::default().csv_row();
+        assert_eq!(header.split(',').count(), row.split(',').count());
+    }
+
+    #[test]
+    fn append_keeps_earlier_rows() {
+        let path = std::env::temp_dir().join(format!("rcw_stats_{}.csv", std::process::id()));
+        let mut w = StatsWriter::create(&path).unwrap();
+        w.write(&Stats::default()).unwrap();
+        w.flush().unwrap();
+        let mut w = StatsWriter::append(&path).unwrap();
+        w.write(&Stats::default()).unwrap();
+        w.flush().unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], Stats::csv_header());
+        assert_eq!(lines[1], lines[2]);
+    }
+}
@@ -0,0 +1,86 @@
+use ndarray_rand::rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// Passes of blurring over the random heights rock is cut from, so it forms outcrops rather than
+// scattered tiles.
+const SMOOTHING: usize = 3;
+
+/// What a tile of the world is made of.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Terrain {
+    /// Grass grows back after being eaten.
+    Meadow,
+    /// Bare rock, no grass grows on it.
+    Rock,
+}
+
+impl Terrain {
+    /// Most grass a tile of this terrain holds, given the value of fully grown grass.
+    pub fn grass_max(self, grass_max: u32) -> u32 {
+        match self {
+            Terrain::Meadow => grass_max,
+            Terrain::Rock => 0,
+        }
+    }
+
+    /// Terrain of an `x` by `y` toroidal world in row-major order, drawn from `rng`: rock
+    /// outcrops covering a fraction `rock` (0-1) of the tiles, meadow elsewhere.
+    pub fn generate<R: Rng + ?Sized>(x: usize, y: usize, rock: f64, rng: &mut R) -> Vec<Terrain> {
+        let n = x * y;
+        let rocks = (rock * n as f64).round() as usize;
+        if rocks == 0 {
+            return vec![Terrain::Meadow; n];
+        }
+
+        let mut height = (0..n).map(|_| rng.gen::<f64>()).collect::<Vec<_>>();
+        (0..SMOOTHING).for_each(|_| {
+            height = (0..n)
+                .map(|i| {
+                    let (tx, ty) = (i % x, i / x);
+                    let neighbors = (0..9).map(|k| {
+                        let nx = (tx + x + k % 3 - 1) % x;
+                        let ny = (ty + y + k / 3 - 1) % y;
+                        height[ny * x + nx]
+                    });
+                    neighbors.sum::<f64>() / 9.
+                })
+                .collect();
+        });
+
+        // The highest tiles are rock.
+        let mut order = (0..n).collect::<Vec<_>>();
+        order.sort_by(|a, b| height[*b].partial_cmp(&height[*a]).unwrap());
+        let mut terrain = vec![Terrain::Meadow; n];
+        order[..rocks.min(n)]
+            .iter()
+            .for_each(|i| terrain[*i] = Terrain::Rock);
+        terrain
+    }
+}
+
+#[cfg(test)]
+mod terrain_tests {
+    use super::*;
+    use ndarray_rand::rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn rock_forms_outcrops() {
+        let generate = |rock| Terrain::generate(40, 30, rock, &mut ChaCha8Rng::seed_from_u64(0));
+        assert!(generate(0.).iter().all(|t| *t == Terrain::Meadow));
+        assert!(generate(1.).iter().all(|t| *t == Terrain::Rock));
+
+        let terrain = generate(0.25);
+        assert_eq!(terrain, generate(0.25));
+        let is_rock = |x: usize, y: usize| terrain[(y % 30) * 40 + x % 40] == Terrain::Rock;
+        let rocks = (0..40 * 30).filter(|i| is_rock(i % 40, i / 40)).count();
+        assert_eq!(rocks, 300);
+
+        // Rock lies next to rock far more often than a quarter of the time.
+        let next_to_rock = (0..40 * 30)
+            .filter(|i| is_rock(i % 40, i / 40) && is_rock(i % 40 + 1, i / 40))
+            .count();
+        assert!(next_to_rock as f64 > 0.5 * rocks as f64);
+    }
+}
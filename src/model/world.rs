@@ -1,11 +1,31 @@
-use crate::model::creature::{Creature, Observation};
+use crate::model::batch::BrainBatch;
+use crate::model::brain::Brain;
+use crate::model::config::WorldConfig;
+use crate::model::creature::{Creature, Observation, VisionChannel};
+use crate::model::events::{Event, EventKind, EventSink};
+use crate::model::lineage::{Lineage, LineageNode};
+use crate::model::species::Species;
+use crate::model::stats::{BrainPopulation, DeathCause, Quantiles, Stats, TickCounts};
+use crate::model::terrain::Terrain;
 use crate::model::{Action, MovementAction, TurningAction};
 use crate::util::gridlookup::GridLookup;
-use ndarray_rand::rand::Rng;
+use ndarray_rand::rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::cmp::min;
 use std::collections::HashSet;
+use std::sync::Mutex;
 
+// Width in radians of the cone in front of a creature within which it bites.
+const BITE_RANGE: f64 = std::f64::consts::FRAC_PI_2;
+
+/// A toroidal grass field populated by creatures.
+///
+/// Each tile of the `x` by `y` field holds a grass value which regrows every tick and is eaten by
+/// creatures standing on it, unless the tile is [rock](Terrain::Rock). Creature positions are
+/// continuous and wrap around the edges.
+#[derive(Serialize, Deserialize)]
 pub struct World {
     creatures: Option<Vec<Creature>>,
     gridlookup: GridLookup<usize>,
@@ -13,105 +33,301 @@ pub struct World {
     grass_values: Vec<u32>,
     grass_tile_x: usize,
     grass_tile_y: usize,
-    grass_max: u32,
-    grass_recharge: u32,
+    // Laid out like grass_values.
+    terrain: Vec<Terrain>,
+    // Scent on each tile, laid out like grass_values.
+    scent_values: Vec<f64>,
+    #[serde(skip)]
+    scent_buff: Vec<f64>,
 
     creature_id: usize,
     min_pop: usize,
     min_fams: usize,
-}
 
-const GRASS_MAX: u32 = 512;
-const BITE_DIST: f64 = 1.0;
-const BITE_DIST_2: f64 = BITE_DIST * BITE_DIST;
-const BITE_DAMAGE: u32 = Creature::STARTING_ENERGY;
-const GRASS_EAT_FRAC: f64 = 0.5;
-const TURN_SPEED: f64 = 0.01;
-const WALK_SPEED: f64 = 0.02;
+    seed: u64,
+    rng: ChaCha8Rng,
 
-const CREATURE_ENERGY_COST: u32 = 1;
-const CREATURE_WALK_ENERGY_COST: u32 = 3;
+    config: WorldConfig,
 
-const MAX_AGE: u32 = 60000;
+    tick: u64,
+    counts: TickCounts,
+    lineage: Lineage,
+    species: Species,
+
+    // Behind a lock only so the world stays `Sync`, it is never contended.
+    #[serde(skip)]
+    sinks: Mutex<Vec<Box<dyn EventSink>>>,
+    #[serde(skip)]
+    batch: BrainBatch,
+}
 
 impl World {
+    /// Make an empty world of `x` by `y` grass tiles, fully grown where they aren't rock.
+    ///
+    /// Creatures are spawned on the first [`World::update`] until there are at least `min_pop`.
+    /// The seed is chosen at random, see [`World::new_seeded`] for reproducible runs.
     pub fn new(x: usize, y: usize, min_pop: usize) -> Self {
-        let grass_max = GRASS_MAX;
-        let xstep = Observation::MAX_DIST as f64;
+        Self::new_seeded(x, y, min_pop, ndarray_rand::rand::thread_rng().gen())
+    }
+
+    /// Like [`World::new`], but every random draw of the run derives from `seed`.
+    ///
+    /// Two worlds with the same arguments evolve identically, regardless of thread count.
+    pub fn new_seeded(x: usize, y: usize, min_pop: usize, seed: u64) -> Self {
+        Self::new_with_config(x, y, min_pop, seed, WorldConfig::default())
+    }
+
+    /// Like [`World::new_seeded`], with constants taken from `config` rather than the defaults.
+    pub fn new_with_config(
+        x: usize,
+        y: usize,
+        min_pop: usize,
+        seed: u64,
+        config: WorldConfig,
+    ) -> Self {
+        let xstep = Observation::MAX_DIST;
         let ystep = xstep;
+        // Its own stream, so the terrain doesn't shift the draws of the run.
+        let mut terrain_rng = ChaCha8Rng::seed_from_u64(seed);
+        terrain_rng.set_stream(1);
+        let terrain = Terrain::generate(x, y, config.terrain_rock, &mut terrain_rng);
         World {
             creatures: Some(vec![]),
             gridlookup: GridLookup::new(x as f64, y as f64, xstep, ystep),
-            grass_values: vec![grass_max; x * y],
+            grass_values: terrain
+                .iter()
+                .map(|t| t.grass_max(config.grass_max))
+                .collect(),
             grass_tile_x: x,
             grass_tile_y: y,
-            grass_max,
-            grass_recharge: 1,
+            terrain,
+            scent_values: vec![0.; x * y],
+            scent_buff: vec![],
             creature_id: 0,
             min_pop,
             min_fams: 5,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            config,
+            tick: 0,
+            counts: TickCounts::default(),
+            lineage: Lineage::default(),
+            species: Species::default(),
+            sinks: Mutex::new(vec![]),
+            batch: BrainBatch::default(),
         }
     }
 
+    /// Size of the world in grass tiles.
     pub fn get_size(&self) -> (usize, usize) {
         (self.grass_tile_x, self.grass_tile_y)
     }
 
+    /// Constants the world runs with.
+    pub fn get_config(&self) -> &WorldConfig {
+        &self.config
+    }
+
+    /// Change the population below which new creatures are spawned.
+    pub fn set_min_pop(&mut self, min_pop: usize) {
+        self.min_pop = min_pop;
+    }
+
+    /// Change the number of families below which new founders are spawned.
+    pub fn set_min_fams(&mut self, min_fams: usize) {
+        self.min_fams = min_fams;
+    }
+
+    /// Number of updates run so far.
+    pub fn get_tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Family tree of the living creatures.
+    pub fn get_lineage(&self) -> &Lineage {
+        &self.lineage
+    }
+
+    /// Founding genomes of the living families.
+    pub fn get_species(&self) -> &Species {
+        &self.species
+    }
+
+    /// Seed the world's random stream started from.
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Receive every event from now on, at the end of each update.
+    ///
+    /// Subscribers are not part of snapshots and need to be added again after loading.
+    pub fn subscribe(&mut self, sink: Box<dyn EventSink>) {
+        self.sinks.get_mut().unwrap().push(sink);
+    }
+
+    /// Flush every subscriber, returning the first error.
+    pub fn flush_events(&mut self) -> std::io::Result<()> {
+        let mut res = Ok(());
+        self.sinks.get_mut().unwrap().iter_mut().for_each(|s| {
+            let r = s.flush();
+            if res.is_ok() {
+                res = r;
+            }
+        });
+        res
+    }
+
+    /// Reserve a fresh creature id.
     pub fn get_inc_creature_id(&mut self) -> usize {
         let t = self.creature_id;
         self.creature_id += 1;
         t
     }
 
+    /// Add a creature to the world, its id should come from [`World::get_inc_creature_id`].
+    ///
+    /// It is recorded in the lineage, and founds its family if that has no founder yet.
     pub fn add_creature(&mut self, c: Creature) {
+        self.lineage.insert(LineageNode::of(&c));
+        if self.species.get_founder(c.get_fam()).is_none() {
+            self.species.found(&c);
+        }
         self.creatures.as_mut().unwrap().push(c)
     }
 
+    /// All living creatures.
     pub fn get_creature_slice(&self) -> &[Creature] {
         self.creatures.as_ref().unwrap()
     }
 
+    /// Find a living creature by id.
+    pub fn get_creature(&self, id: usize) -> Option<&Creature> {
+        self.get_creature_slice().iter().find(|c| c.get_id() == id)
+    }
+
+    /// Grass values of all tiles in row-major order, see [`World::get_grass_loc`].
     pub fn get_grass_slice(&self) -> &[u32] {
         &self.grass_values
     }
 
+    /// Index into [`World::get_grass_slice`] of the tile at `(x, y)`.
     pub fn get_grass_index(&self, x: usize, y: usize) -> usize {
         y * self.grass_tile_x + x
     }
 
+    /// Tile coordinates of the `i`th entry of [`World::get_grass_slice`].
     pub fn get_grass_loc(&self, i: usize) -> (usize, usize) {
         let x = i % self.grass_tile_x;
-        let y = i / self.grass_tile_y;
+        let y = i / self.grass_tile_x;
         (x, y)
     }
 
+    /// Terrain of every tile, indexed like [`World::get_grass_slice`].
+    pub fn get_terrain_slice(&self) -> &[Terrain] {
+        &self.terrain
+    }
+
+    /// Scent on every tile, indexed like [`World::get_grass_slice`].
+    pub fn get_scent_slice(&self) -> &[f64] {
+        &self.scent_values
+    }
+
+    /// Value of a fully grown grass tile.
     pub fn get_grass_max(&self) -> u32 {
-        self.grass_max
+        self.config.grass_max
+    }
+
+    /// Red, green and blue (0-1 each) color of the `i`th tile, see [`World::get_grass_slice`],
+    /// from brown when bare to green when fully grown, or grey for rock. Creatures are seen
+    /// against it.
+    pub fn get_grass_color(&self, i: usize) -> [f64; 3] {
+        if self.terrain[i] == Terrain::Rock {
+            return [0.5; 3];
+        }
+        let grown = self.grass_values[i] as f64 / self.config.grass_max as f64;
+        [(1. - grown) * 148. / 255., 94. / 255., 0.]
     }
 
-    pub fn update<R: Rng>(&mut self, mut rng: R) {
+    // Index of the tile at the world position `(x, y)`, wrapping around the edges.
+    fn tile_at(&self, x: f64, y: f64) -> usize {
+        let (world_x, world_y) = (self.grass_tile_x as f64, self.grass_tile_y as f64);
+        let tx = x.rem_euclid(world_x).floor() as usize % self.grass_tile_x;
+        let ty = y.rem_euclid(world_y).floor() as usize % self.grass_tile_y;
+        self.get_grass_index(tx, ty)
+    }
+
+    // Distance (0-1) between the color of `c` and that of the grass under it.
+    fn contrast(&self, c: &Creature) -> f64 {
+        let (x, y, _) = c.get_pos();
+        let grass = self.get_grass_color(self.tile_at(x, y));
+        let d2 = c
+            .get_body_color()
+            .iter()
+            .zip(grass.iter())
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>();
+        (d2 / 3.).sqrt()
+    }
+
+    /// Advance the simulation by one tick.
+    ///
+    /// Grass regrows, every creature observes its surroundings and acts, dead creatures are removed
+    /// and new ones are spawned until the minimum population and family counts are met.
+    pub fn update(&mut self) {
+        self.tick += 1;
+        let tick = self.tick;
+        let record = !self.sinks.get_mut().unwrap().is_empty();
+        let mut events = vec![];
         let world_x = self.grass_tile_x as f64;
         let world_y = self.grass_tile_y as f64;
 
         // Update grass
-        let grass_recharge = self.grass_recharge;
-        let grass_max = self.grass_max;
+        let grass_recharge = self.config.grass_recharge;
+        let grass_max = self.config.grass_max;
         self.grass_values
             .par_iter_mut()
-            .for_each(|g| *g = min(*g + grass_recharge, grass_max));
+            .zip(self.terrain.par_iter())
+            .for_each(|(g, t)| *g = min(*g + grass_recharge, t.grass_max(grass_max)));
+        self.spread_scent();
 
         // Update creatures
         let mut cs = self.creatures.take().unwrap();
 
-        cs = cs
-            .into_par_iter()
-            .map(|mut c| {
-                c.remove_energy(CREATURE_ENERGY_COST);
-                c.inc_age();
-                c
-            })
-            .filter(|c| (c.get_energy() > 0) && (c.get_age() < MAX_AGE))
-            .collect();
+        let energy_cost = self.config.creature_energy_cost;
+        let max_age = self.config.max_age;
+        let config = &self.config;
+        cs.par_iter_mut().for_each(|c| {
+            c.metabolize(energy_cost, config);
+            c.inc_age();
+        });
+        let (alive, dead): (Vec<_>, Vec<_>) = cs
+            .into_iter()
+            .partition(|c| (c.get_energy() > 0) && (c.get_age() < max_age));
+        cs = alive;
+        dead.iter().for_each(|c| {
+            let cause = if c.get_age() >= max_age {
+                DeathCause::OldAge
+            } else if c.get_killed_by().is_some() {
+                DeathCause::Predation
+            } else {
+                DeathCause::Starvation
+            };
+            self.counts.count_death(cause);
+            self.lineage.record_death(c.get_id(), self.tick);
+            if record {
+                let (x, y, _) = c.get_pos();
+                events.push(Event {
+                    tick,
+                    kind: EventKind::Death {
+                        id: c.get_id(),
+                        age: c.get_age(),
+                        cause,
+                        killer: c.get_killed_by(),
+                        pos: (x, y),
+                    },
+                });
+            }
+        });
 
         cs.iter().enumerate().for_each(|(indx, c)| {
             let (x, y, _) = c.get_pos();
@@ -122,12 +338,16 @@ impl World {
             .par_iter()
             .map(|c| self.observe(c, &cs, &self.gridlookup))
             .collect::<Vec<_>>();
-        let actions: Vec<(TurningAction, MovementAction, Action)> = cs
-            .par_iter_mut()
-            .zip(observations.into_par_iter())
-            .map(|(c, o)| c.get_preferred_action(o))
-            .collect();
+        let actions: Vec<(TurningAction, MovementAction, Action)> = self
+            .batch
+            .get_preferred_actions(&mut cs, observations, self.config.action_selection);
+        actions
+            .iter()
+            .for_each(|(t, m, a)| self.counts.count_actions(*t, *m, *a));
 
+        let walk_speed = self.config.walk_speed;
+        let walk_energy_cost = self.config.creature_walk_energy_cost;
+        let turn_speed = self.config.turn_speed;
         cs.par_iter_mut()
             .zip(actions.par_iter())
             .for_each(|(c, (turn_act, move_act, _))| {
@@ -137,8 +357,8 @@ impl World {
                     MovementAction::WAIT => {}
                     MovementAction::FORWARD => {
                         let (x, y) = c.get_pos_mut();
-                        *x += WALK_SPEED * tc;
-                        *y += WALK_SPEED * ts;
+                        *x += walk_speed * tc;
+                        *y += walk_speed * ts;
                         if *x < 0. {
                             *x = world_x - 0.001;
                         }
@@ -151,23 +371,34 @@ impl World {
                         if *y > world_y {
                             *y = 0.;
                         }
-                        c.remove_energy(CREATURE_WALK_ENERGY_COST);
+                        c.remove_energy(walk_energy_cost);
                     }
                 };
 
                 match turn_act {
                     TurningAction::WAIT => {}
-                    TurningAction::LEFT => c.set_theta(t + TURN_SPEED),
-                    TurningAction::RIGHT => c.set_theta(t - TURN_SPEED),
+                    TurningAction::LEFT => c.set_theta(t + turn_speed),
+                    TurningAction::RIGHT => c.set_theta(t - turn_speed),
                 };
             });
 
+        // Lay scent where each creature now stands.
+        let scent_cost = self.config.scent_cost;
+        cs.par_iter_mut()
+            .for_each(|c| c.add_upkeep(scent_cost * c.get_emission()));
+        cs.iter().for_each(|c| {
+            let (x, y, _) = c.get_pos();
+            let tile = self.tile_at(x, y);
+            self.scent_values[tile] += self.config.scent_deposit * c.get_emission();
+        });
+
+        let chosen = actions.iter().map(|(_, _, a)| *a).collect::<Vec<_>>();
+        let mut mated = vec![false; cs.len()];
         let mut creatures_to_add = vec![];
         (0..cs.len())
-            .zip(actions.into_iter())
+            .zip(actions.iter())
             .for_each(|(ic, (_, _, action))| {
-                let c = &cs[ic];
-                let (x, y, theta) = c.get_pos();
+                let (x, y, theta) = cs[ic].get_pos();
                 match action {
                     Action::WAIT => {}
                     Action::EAT => {
@@ -175,118 +406,281 @@ impl World {
                         let grass_y = y.floor() as usize % self.grass_tile_y;
                         let indx = self.get_grass_index(grass_x, grass_y);
                         let grass = self.grass_values[indx];
-                        let to_eat = (grass as f64 * GRASS_EAT_FRAC).round() as u32;
+                        let to_eat = (grass as f64 * self.config.grass_eat_frac).round() as u32;
                         self.grass_values[indx] -= to_eat;
 
                         let digested = (to_eat as f64 * cs[ic].get_veg_eff()).round() as u32;
                         cs[ic].add_energy(digested);
+                        if record {
+                            events.push(Event {
+                                tick,
+                                kind: EventKind::Meal {
+                                    id: cs[ic].get_id(),
+                                    grass: to_eat,
+                                    energy: digested,
+                                    pos: (x, y),
+                                },
+                            });
+                        }
                     }
                     Action::REPLICATE => {
-                        if c.get_energy() > Creature::STARTING_ENERGY * 4 {
-                            cs[ic].remove_energy(3 * Creature::STARTING_ENERGY);
-
-                            let mut newc = cs[ic].clone_mutate(self.get_inc_creature_id());
-
-                            let rev_t = (theta + std::f64::consts::PI) % std::f64::consts::TAU;
-                            let (dy, dx) = rev_t.sin_cos();
-                            let dx = dx * 10. * WALK_SPEED;
-                            let dy = dy * 10. * WALK_SPEED;
-                            let (cx, cy) = newc.get_pos_mut();
-                            *cx += dx;
-                            *cy += dy;
-                            newc.set_theta(rev_t);
-                            creatures_to_add.push(newc)
+                        if cs[ic].get_energy() > self.config.replicate_min_energy {
+                            cs[ic].remove_energy(self.config.replicate_cost);
+
+                            let id = self.get_inc_creature_id();
+                            let newc = cs[ic].clone_mutate(id, self.tick, &self.config);
+                            let newc = self.add_newborn(newc, theta);
+                            if record {
+                                events.push(Event {
+                                    tick,
+                                    kind: birth(&newc),
+                                });
+                            }
+                            creatures_to_add.push(newc);
+                        }
+                    }
+                    Action::MATE => {
+                        if mated[ic] || cs[ic].get_energy() <= self.config.mate_min_energy {
+                            return;
+                        }
+                        if let Some(im) = self.find_mate(&cs, &chosen, &mated, ic) {
+                            let id = self.get_inc_creature_id();
+                            let (c, mate) = pair_mut(&mut cs, ic, im);
+                            if let Some(newc) = c.mate(mate, id, self.tick, &self.config) {
+                                c.remove_energy(self.config.mate_cost);
+                                mate.remove_energy(self.config.mate_cost);
+                                mated[ic] = true;
+                                mated[im] = true;
+
+                                let newc = self.add_newborn(newc, theta);
+                                self.counts.matings += 1;
+                                if record {
+                                    events.push(Event {
+                                        tick,
+                                        kind: birth(&newc),
+                                    });
+                                }
+                                creatures_to_add.push(newc);
+                            }
                         }
                     }
                     Action::BITE => {
-                        const VISION_RANGE_2: f64 = Observation::VISION_RANGE / 2.;
+                        const BITE_RANGE_2: f64 = BITE_RANGE / 2.;
+                        let bite_dist_2 = self.config.bite_dist.powi(2);
+                        let bite_damage = self.config.bite_damage;
 
-                        self.gridlookup
-                            .get_within_step((x, y), &mut cs, |cs, (cx, cy, t)| {
+                        let bitten = self.gridlookup.get_within_step(
+                            (x, y),
+                            (&mut cs, vec![]),
+                            |(cs, mut bitten), (cx, cy, t)| {
                                 let t = *t;
                                 if cs[t].get_id() != cs[ic].get_id() {
                                     let d2 = (x - cx).powi(2) + (y - cy).powi(2);
-                                    if d2 <= BITE_DIST_2 {
+                                    if d2 <= bite_dist_2 {
                                         let abs_dtheta = (cy - y).atan2(cx - x);
-                                        let dtheta = (abs_dtheta - (theta - VISION_RANGE_2)
+                                        let dtheta = (abs_dtheta - (theta - BITE_RANGE_2)
                                             + 2. * std::f64::consts::TAU)
                                             % std::f64::consts::TAU;
-                                        if dtheta < Observation::VISION_RANGE {
+                                        if dtheta < BITE_RANGE {
                                             let meat_eff = 1. - cs[ic].get_veg_eff();
                                             let dam =
-                                                (meat_eff * BITE_DAMAGE as f64).round() as u32;
+                                                (meat_eff * bite_damage as f64).round() as u32;
                                             let removed = cs[t].remove_energy(dam);
+                                            if removed > 0 && cs[t].get_energy() == 0 {
+                                                let attacker = cs[ic].get_id();
+                                                cs[t].set_killed_by(attacker);
+                                            }
                                             let digested =
                                                 (removed as f64 * meat_eff).round() as u32;
                                             cs[ic].add_energy(digested);
+                                            if record {
+                                                bitten.push(EventKind::Bite {
+                                                    attacker: cs[ic].get_id(),
+                                                    victim: cs[t].get_id(),
+                                                    damage: removed,
+                                                    energy: digested,
+                                                    pos: (x, y),
+                                                });
+                                            }
                                         }
                                     }
                                 }
-                                cs
-                            });
+                                (cs, bitten)
+                            },
+                        );
+                        events.extend(bitten.1.into_iter().map(|kind| Event { tick, kind }));
                     }
                 }
             });
-        cs.extend(creatures_to_add.into_iter());
+        cs.extend(creatures_to_add);
 
         while cs.len() < self.min_pop {
             let id = self.get_inc_creature_id();
-            let x = rng.gen_range(0. ..self.grass_tile_x as f64);
-            let y = rng.gen_range(0. ..self.grass_tile_y as f64);
-            let t = rng.gen_range(0. ..std::f64::consts::TAU);
-
-            let c = Creature::new(id, id, x, y, t, rng.gen_range(0. ..1.));
+            let c = self.spawn_creature(id);
+            if record {
+                events.push(Event {
+                    tick,
+                    kind: birth(&c),
+                });
+            }
             cs.push(c);
+            self.counts.spawns += 1;
         }
 
-        if self.min_fams > 0 {
-            let mut set = HashSet::new();
-            set.extend(cs.iter().map(|c| c.get_fam()));
-            while set.len() < self.min_fams {
-                let id = self.get_inc_creature_id();
-                let x = rng.gen_range(0. ..self.grass_tile_x as f64);
-                let y = rng.gen_range(0. ..self.grass_tile_y as f64);
-                let t = rng.gen_range(0. ..std::f64::consts::TAU);
-
-                let c = Creature::new(id, id, x, y, t, rng.gen_range(0. ..1.));
-                set.insert(c.get_fam());
-                cs.push(c);
+        let mut fams = HashSet::new();
+        fams.extend(cs.iter().map(|c| c.get_fam()));
+        while fams.len() < self.min_fams {
+            let id = self.get_inc_creature_id();
+            let c = self.spawn_creature(id);
+            fams.insert(c.get_fam());
+            if record {
+                events.push(Event {
+                    tick,
+                    kind: birth(&c),
+                });
             }
+            cs.push(c);
+            self.counts.spawns += 1;
         }
+        self.species.retain(|fam| fams.contains(&fam));
 
         self.gridlookup.clear();
         self.creatures = Some(cs);
+
+        self.sinks.get_mut().unwrap().iter_mut().for_each(|s| {
+            events.iter().for_each(|e| s.handle(e));
+        });
+    }
+
+    /// Place a newborn behind its parent, which faces `theta`, and record its birth.
+    ///
+    /// The newborn founds a new family if it has drifted too far from its family's founder.
+    fn add_newborn(&mut self, mut newc: Creature, theta: f64) -> Creature {
+        let walk_speed = self.config.walk_speed;
+        let rev_t = (theta + std::f64::consts::PI) % std::f64::consts::TAU;
+        let (dy, dx) = rev_t.sin_cos();
+        let dx = dx * 10. * walk_speed;
+        let dy = dy * 10. * walk_speed;
+        let (cx, cy) = newc.get_pos_mut();
+        *cx += dx;
+        *cy += dy;
+        newc.set_theta(rev_t);
+        if let Some(threshold) = self.config.speciation_threshold {
+            let distance = self.species.distance_from_founder(&newc);
+            if distance.map(|d| d > threshold).unwrap_or(true) {
+                newc.set_fam(newc.get_id());
+                self.species.found(&newc);
+                self.counts.speciations += 1;
+            }
+        }
+        self.lineage.insert(LineageNode::of(&newc));
+        self.counts.births += 1;
+        newc
     }
 
+    /// The closest creature within `mate_dist` of `cs[ic]` which also chose to mate this tick,
+    /// has not mated yet and has the energy to.
+    fn find_mate(
+        &self,
+        cs: &[Creature],
+        chosen: &[Action],
+        mated: &[bool],
+        ic: usize,
+    ) -> Option<usize> {
+        let (x, y, _) = cs[ic].get_pos();
+        let mate_dist_2 = self.config.mate_dist.powi(2);
+        let min_energy = self.config.mate_min_energy;
+        self.gridlookup
+            .get_within_step((x, y), None, |best, (cx, cy, t)| {
+                let t = *t;
+                let d2 = (x - cx).powi(2) + (y - cy).powi(2);
+                let willing = t != ic
+                    && chosen[t] == Action::MATE
+                    && !mated[t]
+                    && cs[t].get_energy() > min_energy;
+                match best {
+                    Some((best_d2, _)) if best_d2 <= d2 => best,
+                    _ if willing && d2 <= mate_dist_2 => Some((d2, t)),
+                    _ => best,
+                }
+            })
+            .map(|(_, t)| t)
+    }
+
+    /// A fresh founder at a random location, drawn from the world's random stream.
+    fn spawn_creature(&mut self, id: usize) -> Creature {
+        let rng = &mut self.rng;
+        let x = rng.gen_range(0. ..self.grass_tile_x as f64);
+        let y = rng.gen_range(0. ..self.grass_tile_y as f64);
+        let t = rng.gen_range(0. ..std::f64::consts::TAU);
+        let veg = rng.gen_range(0. ..1.);
+
+        let c = Creature::new(id, id, (x, y, t), veg, self.tick, &self.config, rng);
+        self.lineage.insert(LineageNode::of(&c));
+        self.species.found(&c);
+        c
+    }
+
+    /// What creature `c` sees through its [`Vision`](crate::model::Vision), given all creatures
+    /// `cs` indexed by `grid`.
     pub fn observe(&self, c: &Creature, cs: &[Creature], grid: &GridLookup<usize>) -> Observation {
         const MAX_D2: f64 = Observation::MAX_DIST * Observation::MAX_DIST;
-        const VISION_RANGE_2: f64 = Observation::VISION_RANGE / 2.;
 
+        let vision = c.get_vision();
+        let view_d2 = vision.dist * vision.dist;
+        let min_contrast = self.config.vision_min_contrast;
+        let channels = &self.config.vision_channels;
+        let [rgb, size, heading, diet, grass, terrain] = [
+            VisionChannel::Rgb,
+            VisionChannel::Size,
+            VisionChannel::Heading,
+            VisionChannel::Diet,
+            VisionChannel::Grass,
+            VisionChannel::Terrain,
+        ]
+        .map(|ch| channels.contains(&ch));
         let (x, y, theta) = c.get_pos();
         let mut observation = Observation::new_empty();
 
-        observation.energy = c.get_energy() as f64 / (Creature::STARTING_ENERGY as f64);
+        observation.energy = c.get_energy() as f64 / (self.config.starting_energy as f64);
 
         let mut observation =
             grid.get_within_step((x, y), observation, |mut observation, (cx, cy, t)| {
                 let d2 = (x - cx).powi(2) + (y - cy).powi(2);
-                if d2 <= MAX_D2 {
+                if d2 <= view_d2 {
                     let oc = &cs[*t];
-                    if oc.get_id() != c.get_id() {
+                    let hidden = min_contrast > 0. && self.contrast(oc) < min_contrast;
+                    if oc.get_id() != c.get_id() && !hidden {
                         let abs_dtheta = (cy - y).atan2(cx - x);
-                        let dtheta = (abs_dtheta - (theta - VISION_RANGE_2)
+                        let dtheta = (abs_dtheta - (theta - vision.range / 2.)
                             + 2. * std::f64::consts::TAU)
                             % std::f64::consts::TAU;
 
-                        if dtheta < Observation::VISION_RANGE {
-                            let d_from_left = dtheta / Observation::VISION_RANGE;
-                            let soft_bin = Observation::NUM_SITES as f64 * d_from_left;
-                            let bin = soft_bin.floor() as usize;
+                        if dtheta < vision.range {
+                            let bin = vision.slot(dtheta);
 
                             let d2 = (x - cx).powi(2) + (y - cy).powi(2);
                             if d2 < observation.dists[bin] {
                                 observation.dists[bin] = d2;
                                 observation.colors[bin] = oc.get_color();
+                                if rgb {
+                                    observation.rgb[bin] = oc.get_body_color();
+                                }
+                                if size {
+                                    let r = oc.get_radius(&self.config);
+                                    let angle = 2. * (r / d2.sqrt()).atan();
+                                    observation.sizes[bin] = angle / std::f64::consts::PI;
+                                }
+                                if heading {
+                                    let (_, _, ot) = oc.get_pos();
+                                    let dt = (ot - theta + 3. * std::f64::consts::PI)
+                                        % std::f64::consts::TAU
+                                        - std::f64::consts::PI;
+                                    observation.headings[bin] = dt / std::f64::consts::PI;
+                                }
+                                if diet {
+                                    observation.diets[bin] = oc.get_veg_eff();
+                                }
                             }
                         };
                     }
@@ -318,14 +712,707 @@ impl World {
                 let sel_grass_y = (self.grass_tile_y + gy + iy - mid) % self.grass_tile_y;
                 let grass_indx = self.get_grass_index(sel_grass_x, sel_grass_y);
                 observation.neighboring_grass[Observation::neighbor_index(ix, iy)] =
-                    self.grass_values[grass_indx] as f64 / GRASS_MAX as f64;
+                    self.grass_values[grass_indx] as f64 / self.config.grass_max as f64;
             })
         });
 
+        // Scent here and its gradient turned to our heading, saturating so any amount fits 0-1.
+        let scent_at = |dx: f64, dy: f64| {
+            let s = self.scent_values[self.tile_at(x + dx, y + dy)];
+            s / (1. + s)
+        };
+        observation.scent = scent_at(0., 0.);
+        let gx = (scent_at(1., 0.) - scent_at(-1., 0.)) / 2.;
+        let gy = (scent_at(0., 1.) - scent_at(0., -1.)) / 2.;
+        let (ts, tc) = theta.sin_cos();
+        observation.scent_gradient = [gx * tc + gy * ts, gy * tc - gx * ts];
+
+        if grass || terrain {
+            vision.slots().enumerate().for_each(|(i, slot)| {
+                let (g, rock) = self.cast_ray(x, y, theta + vision.site_angle(i), vision.dist);
+                if grass {
+                    observation.grass[slot] = g;
+                }
+                if terrain {
+                    observation.terrain[slot] = rock;
+                }
+            });
+        }
+
         observation
     }
 
+    // Decay the scent on every tile and spread some of it to the four neighbours.
+    fn spread_scent(&mut self) {
+        let (tx, ty) = (self.grass_tile_x, self.grass_tile_y);
+        let keep = 1. - self.config.scent_decay;
+        let spread = self.config.scent_diffusion;
+        let scent = &self.scent_values;
+        self.scent_buff.resize(scent.len(), 0.);
+        self.scent_buff
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(i, s)| {
+                let (x, y) = (i % tx, i / tx);
+                let neighbors = scent[y * tx + (x + 1) % tx]
+                    + scent[y * tx + (x + tx - 1) % tx]
+                    + scent[((y + 1) % ty) * tx + x]
+                    + scent[((y + ty - 1) % ty) * tx + x];
+                *s = keep * ((1. - spread) * scent[i] + spread / 4. * neighbors);
+            });
+        std::mem::swap(&mut self.scent_values, &mut self.scent_buff);
+    }
+
+    // Mean grass (0-1) and fraction (0-1) of rock of the tiles at about one tile apart along the
+    // ray from `(x, y)` in direction `theta`, out to `dist`.
+    fn cast_ray(&self, x: f64, y: f64, theta: f64, dist: f64) -> (f64, f64) {
+        let (world_x, world_y) = (self.grass_tile_x as f64, self.grass_tile_y as f64);
+        let (ts, tc) = theta.sin_cos();
+        let n = dist.round().max(1.) as usize;
+        let (grass, rock) = (1..=n)
+            .map(|k| {
+                let t = dist * k as f64 / n as f64;
+                let gx = (x + t * tc).rem_euclid(world_x).floor() as usize;
+                let gy = (y + t * ts).rem_euclid(world_y).floor() as usize;
+                let indx = self.get_grass_index(gx % self.grass_tile_x, gy % self.grass_tile_y);
+                let rock = (self.terrain[indx] == Terrain::Rock) as u32;
+                (self.grass_values[indx] as f64, rock as f64)
+            })
+            .fold((0., 0.), |(g, r), (tg, tr)| (g + tg, r + tr));
+        (
+            grass / (n as f64 * self.config.grass_max as f64),
+            rock / n as f64,
+        )
+    }
+
+    /// Summarize the world now, along with the event counts accumulated since the last call.
+    pub fn collect_stats(&mut self) -> Stats {
+        let cs = self.get_creature_slice();
+        Stats {
+            tick: self.tick,
+            population: cs.len(),
+            families: self.num_families(),
+            total_grass: self.grass_values.iter().map(|g| *g as u64).sum(),
+            total_energy: cs.iter().map(|c| c.get_energy() as u64).sum(),
+            veg_eff: Quantiles::from_values(cs.iter().map(|c| c.get_veg_eff()).collect()),
+            age: Quantiles::from_values(cs.iter().map(|c| c.get_age() as f64).collect()),
+            temperature: Quantiles::from_values(cs.iter().map(|c| c.get_temperature()).collect()),
+            brain_mut_rate: Quantiles::from_values(
+                cs.iter().map(|c| c.get_brain_mut_rate()).collect(),
+            ),
+            trait_mut_rate: Quantiles::from_values(
+                cs.iter().map(|c| c.get_trait_mut_rate()).collect(),
+            ),
+            vision_sites: Quantiles::from_values(
+                cs.iter().map(|c| c.get_vision().sites as f64).collect(),
+            ),
+            vision_range: Quantiles::from_values(cs.iter().map(|c| c.get_vision().range).collect()),
+            vision_dist: Quantiles::from_values(cs.iter().map(|c| c.get_vision().dist).collect()),
+            brain_size: Quantiles::from_values(
+                cs.iter().map(|c| c.get_brain().size() as f64).collect(),
+            ),
+            brains: BrainPopulation::count(cs.iter().map(|c| c.get_brain())),
+            counts: std::mem::take(&mut self.counts),
+        }
+    }
+
+    /// Number of living creatures.
     pub fn num_creatures(&self) -> usize {
         self.creatures.as_ref().unwrap().len()
     }
+
+    /// Number of distinct families among the living creatures.
+    pub fn num_families(&self) -> usize {
+        self.get_creature_slice()
+            .iter()
+            .map(|c| c.get_fam())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+}
+
+fn birth(c: &Creature) -> EventKind {
+    let (x, y, _) = c.get_pos();
+    EventKind::Birth {
+        id: c.get_id(),
+        parent: c.get_parent(),
+        other_parent: c.get_other_parent(),
+        fam: c.get_fam(),
+        pos: (x, y),
+    }
+}
+
+// Mutable references to two different elements of `v`.
+fn pair_mut<T>(v: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
+    if i < j {
+        let (a, b) = v.split_at_mut(j);
+        (&mut a[i], &mut b[0])
+    } else {
+        let (a, b) = v.split_at_mut(i);
+        (&mut b[0], &mut a[j])
+    }
+}
+
+#[cfg(test)]
+mod world_tests {
+    use super::*;
+    use crate::model::{AnyBrain, BrainKind, NeuralBrain};
+
+    fn run_fingerprint(seed: u64, ticks: usize) -> Vec<(usize, usize, u32, u32, [u64; 4])> {
+        let mut w = World::new_seeded(30, 30, 20, seed);
+        (0..ticks).for_each(|_| w.update());
+        w.get_creature_slice()
+            .iter()
+            .map(|c| {
+                let (x, y, t) = c.get_pos();
+                let bits = [
+                    x.to_bits(),
+                    y.to_bits(),
+                    t.to_bits(),
+                    c.get_veg_eff().to_bits(),
+                ];
+                (c.get_id(), c.get_fam(), c.get_energy(), c.get_age(), bits)
+            })
+            .collect()
+    }
+
+    fn run_with_threads(
+        threads: usize,
+        seed: u64,
+        ticks: usize,
+    ) -> Vec<(usize, usize, u32, u32, [u64; 4])> {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| run_fingerprint(seed, ticks))
+    }
+
+    // A world whose creatures replicate cheaply, so that offspring show up within `ticks`.
+    fn breeding_world(config: WorldConfig, seed: u64, ticks: usize) -> World {
+        let config = WorldConfig {
+            replicate_min_energy: 5000,
+            replicate_cost: 1000,
+            ..config
+        };
+        let mut w = World::new_with_config(30, 30, 20, seed, config);
+        (0..ticks).for_each(|_| w.update());
+        w
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        assert_eq!(run_fingerprint(42, 500), run_fingerprint(42, 500));
+    }
+
+    #[test]
+    fn different_seed_different_run() {
+        assert_ne!(run_fingerprint(1, 10), run_fingerprint(2, 10));
+    }
+
+    #[test]
+    fn lineage_tracks_living() {
+        let mut w = World::new_seeded(30, 30, 20, 11);
+        (0..500).for_each(|_| w.update());
+        let lineage = w.get_lineage();
+        w.get_creature_slice().iter().for_each(|c| {
+            let node = lineage.get(c.get_id()).unwrap();
+            assert_eq!(node.death_tick, None);
+            assert_eq!(node.birth_tick, c.get_birth_tick());
+            // Mating counts generations from the older parent, which need not be the one the
+            // tree follows.
+            let ancestors = lineage.ancestors(c.get_id());
+            let mated = std::iter::once(c.get_id())
+                .chain(ancestors.iter().copied())
+                .any(|id| lineage.get(id).unwrap().other_parent.is_some());
+            if mated {
+                assert!(ancestors.len() <= c.get_generation() as usize);
+            } else {
+                assert_eq!(ancestors.len(), c.get_generation() as usize);
+            }
+        });
+        // Spawned founders which died without children are forgotten.
+        assert_eq!(lineage.len(), w.num_creatures());
+    }
+
+    #[test]
+    fn speciation_splits_families() {
+        let config = WorldConfig {
+            speciation_threshold: Some(1e-9),
+            ..WorldConfig::default()
+        };
+        let mut w = breeding_world(config, 5, 1000);
+        let stats = w.collect_stats();
+        assert!(stats.counts.births > 0);
+        assert_eq!(stats.counts.speciations, stats.counts.births);
+        w.get_creature_slice().iter().for_each(|c| {
+            assert_eq!(c.get_fam(), c.get_id());
+            assert_eq!(w.get_lineage().get(c.get_id()).unwrap().fam, c.get_id());
+        });
+        assert_eq!(w.get_species().len(), w.num_families());
+    }
+
+    #[test]
+    fn no_speciation_by_default() {
+        let mut w = breeding_world(WorldConfig::default(), 5, 1000);
+        let stats = w.collect_stats();
+        assert!(stats.counts.births > 0);
+        assert_eq!(stats.counts.speciations, 0);
+        assert_eq!(w.get_species().len(), w.num_families());
+    }
+
+    #[test]
+    fn mating_records_both_parents() {
+        let config = WorldConfig {
+            replicate_min_energy: u32::MAX,
+            mate_dist: 5.,
+            mate_min_energy: 1000,
+            mate_cost: 500,
+            ..WorldConfig::default()
+        };
+        let mut w = World::new_with_config(30, 30, 20, 5, config);
+        let (tx, rx) = std::sync::mpsc::channel();
+        w.subscribe(Box::new(tx));
+        // Cheap mating can take off, a few hundred creatures are plenty.
+        for _ in 0..300 {
+            if w.num_creatures() >= 300 {
+                break;
+            }
+            w.update();
+        }
+        let stats = w.collect_stats();
+        assert!(stats.counts.matings > 0);
+        assert_eq!(stats.counts.matings, stats.counts.births);
+        let mated_births = rx
+            .try_iter()
+            .filter(|e| {
+                matches!(
+                    e.kind,
+                    EventKind::Birth {
+                        other_parent: Some(_),
+                        ..
+                    }
+                )
+            })
+            .count() as u64;
+        assert_eq!(mated_births, stats.counts.matings);
+
+        let lineage = w.get_lineage();
+        w.get_creature_slice()
+            .iter()
+            .filter(|c| c.get_parent().is_some())
+            .for_each(|c| {
+                let other = c.get_other_parent().unwrap();
+                assert_ne!(c.get_parent(), Some(other));
+                assert_eq!(lineage.get(c.get_id()).unwrap().other_parent, Some(other));
+                assert!(lineage
+                    .get(other)
+                    .unwrap()
+                    .other_children
+                    .contains(&c.get_id()));
+            });
+    }
+
+    #[test]
+    fn stochastic_selection_is_seeded() {
+        let run = |seed| {
+            let config = WorldConfig {
+                action_selection: crate::model::ActionSelection::Softmax,
+                ..WorldConfig::default()
+            };
+            breeding_world(config, seed, 500)
+        };
+        let fingerprint = |w: &World| {
+            w.get_creature_slice()
+                .iter()
+                .map(|c| {
+                    (
+                        c.get_id(),
+                        c.get_pos().0.to_bits(),
+                        c.get_temperature().to_bits(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let (mut a, b) = (run(3), run(3));
+        assert_eq!(fingerprint(&a), fingerprint(&b));
+        assert_ne!(fingerprint(&a), fingerprint(&run(4)));
+
+        let stats = a.collect_stats();
+        assert!(stats.counts.births > 0);
+        assert!(stats.temperature.min < stats.temperature.max);
+    }
+
+    #[test]
+    fn mixed_brains_compete() {
+        let config = WorldConfig {
+            brain_mix: vec![BrainKind::FeedForward, BrainKind::Gru, BrainKind::Neat],
+            ..WorldConfig::default()
+        };
+        let mut w = World::new_with_config(30, 30, 30, 6, config);
+        w.update();
+        let id = w.get_inc_creature_id();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let c = Creature::new(id, id, (1., 1., 0.), 0.5, 1, w.get_config(), &mut rng);
+        let brain = NeuralBrain::new_random(&[2], &[], &mut rng);
+        w.add_creature(c.with_brain(AnyBrain::custom(brain)));
+
+        (0..50).for_each(|_| w.update());
+        let stats = w.collect_stats();
+        assert!(stats.brains.feed_forward > 0 && stats.brains.gru > 0 && stats.brains.neat > 0);
+        assert_eq!(stats.brains.custom, 1);
+        assert!(matches!(
+            w.save_snapshot(vec![]),
+            Err(crate::model::SnapshotError::Encoding(_))
+        ));
+    }
+
+    #[test]
+    fn scripted_creatures_compete() {
+        let config = WorldConfig {
+            brain_mix: vec![BrainKind::Grazer, BrainKind::Predator],
+            ..WorldConfig::default()
+        };
+        let mut w = World::new_with_config(30, 30, 40, 8, config);
+        (0..1000).for_each(|_| w.update());
+        let stats = w.collect_stats();
+        assert!(stats.brains.grazer > 0 && stats.brains.predator > 0);
+        assert!(stats.counts.actions[Action::EAT as usize] > 0);
+        assert!(stats.counts.deaths_predation > 0);
+        assert_eq!(stats.brain_size.max, 0.);
+    }
+
+    // What a creature at (10, 10) facing along x sees of another one at `other`, of body color
+    // `color` if set.
+    fn sees(
+        config: WorldConfig,
+        other: (f64, f64, f64),
+        color: Option<[f64; 3]>,
+    ) -> (Observation, Creature) {
+        let w = World::new_with_config(30, 30, 0, 13, config.clone());
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut cs = [(10., 10., 0.), other]
+            .iter()
+            .enumerate()
+            .map(|(id, pos)| Creature::new(id, id, *pos, 0.5, 0, &config, &mut rng))
+            .collect::<Vec<_>>();
+        let mut grid = GridLookup::new(30., 30., Observation::MAX_DIST, Observation::MAX_DIST);
+        cs.iter().enumerate().for_each(|(i, c)| {
+            let (x, y, _) = c.get_pos();
+            grid.put((x, y), i);
+        });
+        if let Some(color) = color {
+            let other = cs.pop().unwrap().with_body_color(color);
+            cs.push(other);
+        }
+        (w.observe(&cs[0], &cs, &grid), cs.pop().unwrap())
+    }
+
+    #[test]
+    fn vision_shapes_observations() {
+        let sees = |vision_sites, vision_dist, (x, y)| {
+            let config = WorldConfig {
+                vision_sites,
+                vision_dist,
+                ..WorldConfig::default()
+            };
+            sees(config, (x, y, 0.), None).0
+        };
+        let mid = Observation::MAX_SITES / 2;
+        let o = sees(5, 10., (15., 10.));
+        assert_eq!(o.dists[mid], 5.);
+        assert!(o.colors[mid] > 0.);
+        let seen = o.dists.iter().filter(|d| **d < Observation::MAX_DIST);
+        assert_eq!(seen.count(), 1);
+
+        let o = sees(5, 4., (15., 10.));
+        assert!(o.dists.iter().all(|d| *d == Observation::MAX_DIST));
+
+        // Just left of straight ahead, in the middle site of three but the next of nine.
+        let o = sees(3, 10., (15., 11.));
+        assert!(o.dists[mid] < Observation::MAX_DIST);
+        let o = sees(9, 10., (15., 11.));
+        assert!(o.dists[mid + 1] < Observation::MAX_DIST);
+    }
+
+    #[test]
+    fn vision_channels() {
+        let mid = Observation::MAX_SITES / 2;
+        let (o, _) = sees(WorldConfig::default(), (15., 10., 1.), None);
+        assert_eq!(o.rgb[mid], [0.; 3]);
+        assert_eq!([o.sizes[mid], o.headings[mid], o.diets[mid]], [0.; 3]);
+
+        let config = WorldConfig {
+            vision_channels: vec![
+                VisionChannel::Rgb,
+                VisionChannel::Size,
+                VisionChannel::Heading,
+                VisionChannel::Diet,
+            ],
+            ..WorldConfig::default()
+        };
+        let (o, other) = sees(
+            config.clone(),
+            (15., 10., std::f64::consts::FRAC_PI_2),
+            None,
+        );
+        assert_eq!(o.rgb[mid], other.get_body_color());
+        assert!((o.headings[mid] - 0.5).abs() < 1e-9);
+        assert_eq!(o.diets[mid], 0.5);
+        let (near, _) = sees(config, (12., 10., 0.), None);
+        assert!(near.sizes[mid] > o.sizes[mid] && o.sizes[mid] > 0.);
+        assert!(near.headings[mid].abs() < 1e-9);
+        assert_eq!(o.rgb[mid - 1], [0.; 3]);
+    }
+
+    #[test]
+    fn grass_rays() {
+        let observe = |channels: Vec<VisionChannel>| {
+            let config = WorldConfig {
+                vision_channels: channels,
+                ..WorldConfig::default()
+            };
+            let mut w = World::new_with_config(30, 30, 0, 15, config.clone());
+            // Only the row ahead of the creature is grown.
+            w.grass_values.iter_mut().for_each(|g| *g = 0);
+            (11..21).for_each(|x| {
+                let i = w.get_grass_index(x, 10);
+                w.grass_values[i] = config.grass_max;
+            });
+            let mut rng = ChaCha8Rng::seed_from_u64(0);
+            let c = Creature::new(0, 0, (10.5, 10.5, 0.), 0.5, 0, &config, &mut rng);
+            let grid = GridLookup::new(30., 30., Observation::MAX_DIST, Observation::MAX_DIST);
+            (w.observe(&c, &[], &grid), c.get_vision().slots())
+        };
+
+        let (o, slots) = observe(vec![VisionChannel::Grass]);
+        let mid = Observation::MAX_SITES / 2;
+        assert_eq!(o.grass[mid], 1.);
+        assert!(o.grass[slots.start] < 0.5 && o.grass[slots.end - 1] < 0.5);
+        assert_eq!(o.grass[slots.start - 1], 0.);
+
+        let (o, _) = observe(vec![]);
+        assert!(o.grass.iter().all(|g| *g == 0.));
+    }
+
+    #[test]
+    fn rock_grows_no_grass() {
+        let config = WorldConfig {
+            terrain_rock: 0.3,
+            vision_channels: vec![VisionChannel::Terrain],
+            ..WorldConfig::default()
+        };
+        let mut w = World::new_with_config(30, 30, 0, 16, config.clone());
+        w.grass_values.iter_mut().for_each(|g| *g = 0);
+        w.update();
+        let rock = |t: &Terrain| *t == Terrain::Rock;
+        assert_eq!(
+            w.get_terrain_slice().iter().filter(|t| rock(t)).count(),
+            270
+        );
+        assert!(w
+            .get_grass_slice()
+            .iter()
+            .zip(w.get_terrain_slice())
+            .all(|(g, t)| (*g == 0) == rock(t)));
+
+        // Only the row ahead of the creature is rock.
+        w.terrain = vec![Terrain::Meadow; 30 * 30];
+        (11..21).for_each(|x| {
+            let i = w.get_grass_index(x, 10);
+            w.terrain[i] = Terrain::Rock;
+        });
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let c = Creature::new(0, 0, (10.5, 10.5, 0.), 0.5, 0, &config, &mut rng);
+        let grid = GridLookup::new(30., 30., Observation::MAX_DIST, Observation::MAX_DIST);
+        let (o, slots) = (w.observe(&c, &[], &grid), c.get_vision().slots());
+        let mid = Observation::MAX_SITES / 2;
+        assert_eq!(o.terrain[mid], 1.);
+        assert!(o.terrain[slots.start] < 0.5);
+        assert_eq!(o.terrain[slots.start - 1], 0.);
+        assert!(o.grass.iter().all(|g| *g == 0.));
+    }
+
+    #[test]
+    fn camouflage_hides_creatures() {
+        let mid = Observation::MAX_SITES / 2;
+        let config = WorldConfig {
+            vision_min_contrast: 0.1,
+            ..WorldConfig::default()
+        };
+        let grass = [0., 94. / 255., 0.];
+        let (o, _) = sees(config.clone(), (15., 10., 0.), Some(grass));
+        assert_eq!(o.dists[mid], Observation::MAX_DIST);
+        let (o, _) = sees(config, (15., 10., 0.), Some([1., 0., 1.]));
+        assert_eq!(o.dists[mid], 5.);
+        assert!((o.colors[mid] - 0.413).abs() < 1e-9);
+    }
+
+    #[test]
+    fn body_color_is_inherited() {
+        let config = WorldConfig::default();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut c = Creature::new(0, 7, (0., 0., 0.), 0.5, 0, &config, &mut rng);
+        assert_eq!(c.get_body_color(), crate::model::fam_color(7));
+        let mut child = c.clone_mutate(1, 0, &config);
+        assert_ne!(child.get_body_color(), c.get_body_color());
+        assert!(child
+            .get_body_color()
+            .iter()
+            .all(|v| (0. ..=1.).contains(v)));
+        let color = child.get_body_color();
+        child.set_fam(1);
+        assert_eq!(child.get_body_color(), color);
+    }
+
+    #[test]
+    fn vision_costs_energy() {
+        let config = WorldConfig {
+            vision_area_cost: 0.,
+            vision_site_cost: 0.1,
+            ..WorldConfig::default()
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut c = Creature::new(0, 0, (0., 0., 0.), 0.5, 0, &config, &mut rng);
+        let start = c.get_energy();
+        (0..10).for_each(|_| c.metabolize(1, &config));
+        assert_eq!(start - c.get_energy(), 10 + 5);
+
+        let wide = WorldConfig {
+            vision_range: std::f64::consts::PI,
+            ..WorldConfig::default()
+        };
+        let narrow = WorldConfig::default();
+        assert!(wide.vision().upkeep(&wide) > narrow.vision().upkeep(&narrow));
+    }
+
+    #[test]
+    fn scent_spreads_and_decays() {
+        let mut w = World::new_with_config(10, 10, 0, 0, WorldConfig::default());
+        let i = w.get_grass_index(0, 5);
+        w.scent_values[i] = 1.;
+        w.update();
+        let scent = w.get_scent_slice();
+        let total = scent.iter().sum::<f64>();
+        assert!((total - (1. - w.config.scent_decay)).abs() < 1e-9);
+        assert!(scent[i] < 1.);
+        // Spread around the edge of the world too.
+        assert!(scent[w.get_grass_index(9, 5)] > 0.);
+        assert_eq!(scent[w.get_grass_index(2, 5)], 0.);
+    }
+
+    #[test]
+    fn scent_is_sensed() {
+        let config = WorldConfig::default();
+        let mut w = World::new_with_config(30, 30, 0, 0, config.clone());
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let theta = std::f64::consts::FRAC_PI_2;
+        let cs = [Creature::new(
+            0,
+            0,
+            (10.5, 10.5, theta),
+            0.5,
+            0,
+            &config,
+            &mut rng,
+        )];
+        let grid = GridLookup::new(30., 30., Observation::MAX_DIST, Observation::MAX_DIST);
+        let here = w.get_grass_index(10, 10);
+        let ahead = w.get_grass_index(10, 11);
+        w.scent_values[here] = 1.;
+        w.scent_values[ahead] = 3.;
+        let o = w.observe(&cs[0], &cs, &grid);
+        assert_eq!(o.scent, 0.5);
+        // Facing up the gradient, so it is all straight ahead.
+        assert!((o.scent_gradient[0] - 0.375).abs() < 1e-9);
+        assert!(o.scent_gradient[1].abs() < 1e-9);
+    }
+
+    #[test]
+    fn vision_evolves() {
+        // Eyes only gain or lose sites now and then, so mutate traits fast.
+        let config = WorldConfig {
+            trait_mut_rate: Some(0.5),
+            ..WorldConfig::default()
+        };
+        let mut w = breeding_world(config, 6, 1000);
+        let stats = w.collect_stats();
+        assert!(stats.vision_sites.min < stats.vision_sites.max);
+        assert!(stats.vision_range.min < stats.vision_range.max);
+        assert!(stats.vision_dist.min < stats.vision_dist.max);
+        assert!(stats.vision_dist.max <= Observation::MAX_DIST);
+        assert!(w
+            .get_creature_slice()
+            .iter()
+            .all(|c| (1..=Observation::MAX_SITES).contains(&c.get_vision().sites)));
+    }
+
+    fn mut_rates(config: WorldConfig) -> (Stats, Vec<(f64, f64)>) {
+        let mut w = breeding_world(config, 5, 1000);
+        let rates = w
+            .get_creature_slice()
+            .iter()
+            .map(|c| (c.get_brain_mut_rate(), c.get_trait_mut_rate()))
+            .collect();
+        (w.collect_stats(), rates)
+    }
+
+    #[test]
+    fn mutation_rates_self_adapt() {
+        let (stats, rates) = mut_rates(WorldConfig::default());
+        assert!(stats.counts.births > 0);
+        assert!(stats.brain_mut_rate.min < stats.brain_mut_rate.max);
+        assert!(rates.iter().all(|(b, t)| b == t));
+
+        let (_, rates) = mut_rates(WorldConfig {
+            trait_mut_rate: Some(0.01),
+            ..WorldConfig::default()
+        });
+        assert!(rates.iter().any(|(b, t)| b != t));
+
+        let (_, rates) = mut_rates(WorldConfig {
+            mut_rate_adaptation: 0.,
+            ..WorldConfig::default()
+        });
+        assert!(rates.iter().all(|(b, t)| *b == 0.05 && *t == 0.05));
+    }
+
+    #[test]
+    fn events_match_counts() {
+        let mut w = breeding_world(WorldConfig::default(), 9, 0);
+        let (tx, rx) = std::sync::mpsc::channel();
+        w.subscribe(Box::new(tx));
+        (0..300).for_each(|_| w.update());
+        let stats = w.collect_stats();
+        let events = rx.try_iter().collect::<Vec<_>>();
+
+        let count = |f: fn(&EventKind) -> bool| events.iter().filter(|e| f(&e.kind)).count() as u64;
+        assert_eq!(
+            count(|k| matches!(
+                k,
+                EventKind::Birth {
+                    parent: Some(_),
+                    ..
+                }
+            )),
+            stats.counts.births
+        );
+        assert_eq!(
+            count(|k| matches!(k, EventKind::Birth { parent: None, .. })),
+            stats.counts.spawns
+        );
+        assert_eq!(
+            count(|k| matches!(k, EventKind::Death { .. })),
+            stats.counts.deaths_starvation
+                + stats.counts.deaths_old_age
+                + stats.counts.deaths_predation
+        );
+        assert!(count(|k| matches!(k, EventKind::Meal { .. })) > 0);
+        assert!(events.windows(2).all(|w| w[0].tick <= w[1].tick));
+    }
+
+    #[test]
+    fn thread_count_independent() {
+        assert_eq!(run_with_threads(1, 7, 500), run_with_threads(4, 7, 500));
+    }
 }
@@ -1,3 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+/// Spatial hash of points on a wrapping `xmax` by `ymax` plane, binned by `xstep` by `ystep`.
+#[derive(Serialize, Deserialize)]
 pub struct GridLookup<T: Copy> {
     xmax: f64,
     ymax: f64,
@@ -38,6 +42,8 @@ impl<T: Copy> GridLookup<T> {
         self.data[indx].push((x, y, val))
     }
 
+    /// Fold over all entries within one step in each direction of `coord`, with coordinates
+    /// unwrapped to be near `coord`.
     pub fn get_within_step<V, F: Fn(V, (f64, f64, &T)) -> V>(
         &self,
         coord: (f64, f64),
@@ -103,7 +109,7 @@ mod grid_tests {
 
     #[test]
     fn basic_test() {
-        let mut grid = GridLookup::<usize>::new(10., 10., 1., 1.);
+        let grid = GridLookup::<usize>::new(10., 10., 1., 1.);
         let count = grid.get_within_step((0., 0.), 0, |acc, _| acc + 1);
         assert_eq!(count, 0)
     }
@@ -1 +1,3 @@
+//! Helpers not tied to the simulation itself.
+
 pub mod gridlookup;
@@ -1,13 +1,10 @@
-use crate::model::{Observation, World};
+use rust_creatures::model::{fam_color, Observation, VisionChannel, World};
 use sdl2::event::EventPollIterator;
 use sdl2::gfx::primitives::DrawRenderer;
 use sdl2::pixels::Color;
 use sdl2::rect::{Point, Rect};
-use sdl2::render::WindowCanvas;
+use sdl2::render::{BlendMode, WindowCanvas};
 use sdl2::EventPump;
-use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
 
 pub struct View {
     canvas: WindowCanvas,
@@ -17,6 +14,9 @@ pub struct View {
     yoff: f64,
 
     draw_vision: bool,
+    // Whether creatures are drawn in their evolved body color rather than their family's.
+    draw_body_color: bool,
+    draw_scent: bool,
 }
 
 impl View {
@@ -42,6 +42,8 @@ impl View {
             xoff: 0.0,
             yoff: 0.0,
             draw_vision: false,
+            draw_body_color: false,
+            draw_scent: false,
         }
     }
 
@@ -49,6 +51,16 @@ impl View {
         self.draw_vision = !self.draw_vision;
     }
 
+    /// Switch between drawing creatures in their family's color and in their evolved body color.
+    pub fn toggle_body_color(&mut self) {
+        self.draw_body_color = !self.draw_body_color;
+    }
+
+    /// Show or hide the scent laid on each tile.
+    pub fn toggle_scent(&mut self) {
+        self.draw_scent = !self.draw_scent;
+    }
+
     pub fn get_scaling(&self) -> f64 {
         self.scaling
     }
@@ -82,36 +94,44 @@ impl View {
         self.canvas.set_draw_color(Color::RGB(0, 0, 0));
         self.canvas.clear();
 
-        // Draw grass
+        // Draw grass, with scent blended over it
         let scaling = self.scaling;
-        let grass_max = w.get_grass_max() as f64;
-        w.get_grass_slice()
-            .iter()
-            .enumerate()
-            .try_for_each(|(i, v)| -> Result<(), String> {
-                let (x, y) = w.get_grass_loc(i);
-                let rg = *v as f64 / grass_max;
-                let r = ((1. - rg) * 148.) as u8;
-
-                // Only draw squares in bounds.
-                let (canvas_x, canvas_y) = self.map_to_screen(x as f64, y as f64);
-                let x_left = canvas_x + (scaling as i32) >= 0;
-                let x_right = canvas_x <= window_x as i32;
-                let y_top = canvas_y + (scaling as i32) >= 0;
-                let y_bot = canvas_y <= window_y as i32;
-
-                if (x_left || x_right) || (y_top || y_bot) {
-                    self.canvas.set_draw_color(Color::RGB(r, 94, 0));
-                    let r = Rect::new(canvas_x, canvas_y, scaling as u32, scaling as u32);
+        self.canvas.set_blend_mode(BlendMode::Blend);
+        (0..w.get_grass_slice().len()).try_for_each(|i| -> Result<(), String> {
+            let (x, y) = w.get_grass_loc(i);
+
+            // Only draw squares in bounds.
+            let (canvas_x, canvas_y) = self.map_to_screen(x as f64, y as f64);
+            let x_left = canvas_x + (scaling as i32) >= 0;
+            let x_right = canvas_x <= window_x as i32;
+            let y_top = canvas_y + (scaling as i32) >= 0;
+            let y_bot = canvas_y <= window_y as i32;
+
+            if (x_left || x_right) || (y_top || y_bot) {
+                self.canvas.set_draw_color(rgb_color(w.get_grass_color(i)));
+                let r = Rect::new(canvas_x, canvas_y, scaling as u32, scaling as u32);
+                self.canvas.fill_rect(r)?;
+                if self.draw_scent {
+                    let s = w.get_scent_slice()[i];
+                    let alpha = 255. * s / (1. + s);
+                    self.canvas
+                        .set_draw_color(Color::RGBA(255, 0, 255, alpha as u8));
                     self.canvas.fill_rect(r)?;
-                    self.canvas.set_draw_color(Color::RGB(0, 127, 0));
-                    self.canvas.draw_rect(r)
-                } else {
-                    Ok(())
                 }
-            })?;
+                self.canvas.set_draw_color(Color::RGB(0, 127, 0));
+                self.canvas.draw_rect(r)
+            } else {
+                Ok(())
+            }
+        })?;
 
-        let mut fam_color_hash = HashMap::<usize, (u8, u8, u8)>::new();
+        let channels = &w.get_config().vision_channels;
+        let show_rgb = channels.contains(&VisionChannel::Rgb);
+        let show_size = channels.contains(&VisionChannel::Size);
+        let show_heading = channels.contains(&VisionChannel::Heading);
+        let show_diet = channels.contains(&VisionChannel::Diet);
+        let show_grass = channels.contains(&VisionChannel::Grass);
+        let show_terrain = channels.contains(&VisionChannel::Terrain);
 
         // Draw Creatures
         w.get_creature_slice()
@@ -126,28 +146,17 @@ impl View {
                     self.canvas_on_screen(canvas_x - rad, canvas_y - rad, 2 * rad, 2 * rad);
 
                 if is_on_screen {
-                    let fam = c.get_fam();
-                    let ent = fam_color_hash.entry(fam);
-                    let (r, g, b) = *ent.or_insert_with(|| {
-                        let mut s = DefaultHasher::new();
-                        fam.hash(&mut s);
-                        let hashed_fam = s.finish();
-                        let r = hashed_fam & (255);
-                        let g = (hashed_fam & (255 << 8)) >> 8;
-                        let b = (hashed_fam & (255 << 16)) >> 16;
-                        (r as u8, g as u8, b as u8)
-                    });
-
-                    let col = Color::RGB(r as u8, g as u8, b as u8);
+                    let col = if self.draw_body_color {
+                        rgb_color(c.get_body_color())
+                    } else {
+                        rgb_color(fam_color(c.get_fam()))
+                    };
                     let rad = rad as i16;
 
                     self.canvas
                         .filled_circle(canvas_x as i16, canvas_y as i16, rad, col)?;
 
-                    let veg_eff = c.get_veg_eff();
-                    let g = 255. * veg_eff;
-                    let r = 255. * (1. - veg_eff);
-                    let diet_col = Color::RGB(r.round() as u8, g.round() as u8, 0);
+                    let diet_col = diet_color(c.get_veg_eff());
                     let rad = (scaling / 3.) as i16;
                     self.canvas
                         .filled_circle(canvas_x as i16, canvas_y as i16, rad, diet_col)?;
@@ -163,73 +172,83 @@ impl View {
 
                 if self.draw_vision {
                     if let Some(o) = c.get_last_observation() {
-                        o.dists
-                            .iter()
-                            .cloned()
-                            .zip(o.colors.iter().cloned())
-                            .enumerate()
-                            .try_for_each(|(i, (d, c))| {
-                                let dangle =
-                                    Observation::VISION_RANGE / Observation::NUM_SITES as f64;
-                                let angle =
-                                    i as f64 * dangle - Observation::VISION_RANGE / 2.0 + theta;
+                        let vision = c.get_vision();
+                        let dangle = vision.site_width();
+                        vision.slots().enumerate().try_for_each(
+                            |(i, slot)| -> Result<(), String> {
+                                let seen = o.dists[slot] < Observation::MAX_DIST;
+                                let d = o.dists[slot].min(vision.dist);
+                                let angle = i as f64 * dangle - vision.range / 2.0 + theta;
                                 let (a_s, a_c) = angle.sin_cos();
                                 let (a_ds, a_dc) = (angle + dangle).sin_cos();
 
-                                [-world_x, 0., world_x]
-                                    .iter()
-                                    .cloned()
-                                    .try_for_each(|xoff| {
-                                        [-world_y, 0., world_y].iter().cloned().try_for_each(
-                                            |yoff| {
-                                                let start_x_world = x + xoff + d * a_c;
-                                                let start_y_world = y + yoff + d * a_s;
-                                                let end_x_world = x + xoff + d * a_dc;
-                                                let end_y_world = y + yoff + d * a_ds;
-
-                                                let start_in_x_world =
-                                                    start_x_world >= 0. && start_x_world <= world_x;
-                                                let start_in_y_world =
-                                                    start_y_world >= 0. && start_y_world <= world_y;
-                                                let start_in_world =
-                                                    start_in_x_world && start_in_y_world;
-                                                let end_in_x_world =
-                                                    end_x_world >= 0. && end_x_world <= world_x;
-                                                let end_in_y_world =
-                                                    end_y_world >= 0. && end_y_world <= world_y;
-                                                let end_in_world = end_in_x_world && end_in_y_world;
-                                                if start_in_world || end_in_world {
-                                                    let (startx, starty) = self.map_to_screen(
-                                                        start_x_world,
-                                                        start_y_world,
-                                                    );
-
-                                                    let (endx, endy) = self
-                                                        .map_to_screen(end_x_world, end_y_world);
-
-                                                    let start_on_screen =
-                                                        self.canvas_on_screen(startx, starty, 0, 0);
-                                                    let end_on_screen =
-                                                        self.canvas_on_screen(startx, starty, 0, 0);
-
-                                                    if start_on_screen || end_on_screen {
-                                                        let start = Point::new(startx, starty);
-                                                        let end = Point::new(endx, endy);
-
-                                                        let v = (255. * c).floor() as u8;
-                                                        let col = Color::RGB(v, v, v);
-                                                        self.canvas.set_draw_color(col);
-                                                        self.canvas.draw_line(start, end)
-                                                    } else {
-                                                        Ok(())
-                                                    }
-                                                } else {
-                                                    Ok(())
-                                                }
-                                            },
-                                        )
-                                    })
-                            })?;
+                                // A ray through the middle of the site out to the view distance,
+                                // green with the grass along it and grey with the rock.
+                                if show_grass || show_terrain {
+                                    let (m_s, m_c) = (angle + dangle / 2.).sin_cos();
+                                    let grass = if show_grass { o.grass[slot] } else { 0. };
+                                    let rock = if show_terrain { o.terrain[slot] } else { 0. };
+                                    let grey = 0.5 * rock;
+                                    let ray_col =
+                                        rgb_color([grey, grey + (1. - rock) * grass, grey]);
+                                    self.draw_wrapped_line(
+                                        (x, y),
+                                        (x + vision.dist * m_c, y + vision.dist * m_s),
+                                        1,
+                                        ray_col,
+                                        (world_x, world_y),
+                                    )?;
+                                }
+
+                                // An arc at the distance seen, in the color seen, as wide as the
+                                // creature seen looks.
+                                let col = if seen && show_rgb {
+                                    rgb_color(o.rgb[slot])
+                                } else {
+                                    let v = (255. * o.colors[slot]).floor() as u8;
+                                    Color::RGB(v, v, v)
+                                };
+                                let width = if seen && show_size {
+                                    1 + (8. * o.sizes[slot]).round() as u8
+                                } else {
+                                    1
+                                };
+                                self.draw_wrapped_line(
+                                    (x + d * a_c, y + d * a_s),
+                                    (x + d * a_dc, y + d * a_ds),
+                                    width,
+                                    col,
+                                    (world_x, world_y),
+                                )?;
+
+                                // A tick from the middle of the arc pointing where the creature
+                                // seen is heading, in the color of its diet.
+                                if seen && (show_heading || show_diet) {
+                                    let mid = angle + dangle / 2.;
+                                    let (m_s, m_c) = mid.sin_cos();
+                                    let from = (x + d * m_c, y + d * m_s);
+                                    let heading = if show_heading {
+                                        theta + o.headings[slot] * std::f64::consts::PI
+                                    } else {
+                                        mid
+                                    };
+                                    let (h_s, h_c) = heading.sin_cos();
+                                    let tick_col = if show_diet {
+                                        diet_color(o.diets[slot])
+                                    } else {
+                                        col
+                                    };
+                                    self.draw_wrapped_line(
+                                        from,
+                                        (from.0 + h_c, from.1 + h_s),
+                                        1,
+                                        tick_col,
+                                        (world_x, world_y),
+                                    )?;
+                                }
+                                Ok(())
+                            },
+                        )?;
                     }
                 }
 
@@ -241,6 +260,43 @@ impl View {
         Ok(())
     }
 
+    // Draw a line between two points in world coordinates, repeated across the wrapping edges
+    // wherever an end lies in the world.
+    fn draw_wrapped_line(
+        &mut self,
+        from: (f64, f64),
+        to: (f64, f64),
+        width: u8,
+        col: Color,
+        world: (f64, f64),
+    ) -> Result<(), String> {
+        let (world_x, world_y) = world;
+        let in_world = |(x, y): (f64, f64)| x >= 0. && x <= world_x && y >= 0. && y <= world_y;
+        [-world_x, 0., world_x].iter().try_for_each(|xoff| {
+            [-world_y, 0., world_y].iter().try_for_each(|yoff| {
+                let start = (from.0 + xoff, from.1 + yoff);
+                let end = (to.0 + xoff, to.1 + yoff);
+                if !in_world(start) && !in_world(end) {
+                    return Ok(());
+                }
+                let (startx, starty) = self.map_to_screen(start.0, start.1);
+                let (endx, endy) = self.map_to_screen(end.0, end.1);
+                if !self.canvas_on_screen(startx, starty, 0, 0)
+                    && !self.canvas_on_screen(endx, endy, 0, 0)
+                {
+                    Ok(())
+                } else if width > 1 {
+                    let (sx, sy, ex, ey) = (startx as i16, starty as i16, endx as i16, endy as i16);
+                    self.canvas.thick_line(sx, sy, ex, ey, width, col)
+                } else {
+                    self.canvas.set_draw_color(col);
+                    self.canvas
+                        .draw_line(Point::new(startx, starty), Point::new(endx, endy))
+                }
+            })
+        })
+    }
+
     #[inline]
     fn map_to_screen(&self, x: f64, y: f64) -> (i32, i32) {
         Self::apply_offsets(x, y, self.xoff, self.yoff, self.scaling)
@@ -254,6 +310,7 @@ impl View {
     }
 
     #[inline]
+    #[allow(dead_code)]
     fn phys_on_screen(&self, x: f64, y: f64, w: f64, h: f64) -> bool {
         let (canvas_x, canvas_y) = self.map_to_screen(x, y);
         let (canvas_dx, canvas_dy) = self.map_to_screen(x + w, y + h);
@@ -275,7 +332,19 @@ impl View {
         (x_left || x_right) && (y_top || y_bot)
     }
 
-    pub fn get_events(&mut self) -> EventPollIterator {
+    pub fn get_events(&mut self) -> EventPollIterator<'_> {
         self.event_pump.poll_iter()
     }
 }
+
+fn rgb_color(rgb: [f64; 3]) -> Color {
+    let [r, g, b] = rgb.map(|v| (255. * v).round() as u8);
+    Color::RGB(r, g, b)
+}
+
+// From red for pure carnivores to green for pure herbivores.
+fn diet_color(veg_eff: f64) -> Color {
+    let g = 255. * veg_eff;
+    let r = 255. * (1. - veg_eff);
+    Color::RGB(r.round() as u8, g.round() as u8, 0)
+}